@@ -0,0 +1,119 @@
+// times the vm's dispatch loop on a few call- and loop-heavy scripts.
+// each script is compiled for and run on a fresh vm several times; the
+// median run time (excluding compiling) is reported, next to the median in
+// dispatch_baseline.txt and how many times faster this run was.
+//
+// run with: cargo bench
+// make this run the new baseline with: cargo bench -- --save-baseline
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use sagelang::compiler::Compiler;
+use sagelang::lexer::{Lexer, Tokenizer};
+use sagelang::parser::Parser;
+use sagelang::vm::{Chunk, VM};
+
+const RUNS: usize = 10;
+const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/dispatch_baseline.txt");
+
+const WORKLOADS: &[(&str, &str)] = &[
+    ("fib_recursive", r#"
+        function fibonacci(n) {
+            if (n <= 1) {
+                return n;
+            }
+            return fibonacci(n - 1) + fibonacci(n - 2);
+        }
+        fibonacci(22);
+    "#),
+    ("while_loop", r#"
+        let total = 0;
+        let i = 0;
+        while (i < 200000) {
+            total = total + i % 7;
+            i = i + 1;
+        }
+    "#),
+    ("for_list", r#"
+        let xs = [];
+        for (i in 1 .. 20000) {
+            xs.push(i * 2);
+        }
+        let sum = 0;
+        for (x in xs) {
+            sum = sum + x;
+        }
+    "#),
+    ("closures", r#"
+        function counter() {
+            let count = 0;
+            return function () {
+                count = count + 1;
+                return count;
+            };
+        }
+        let next = counter();
+        for (i in 1 .. 50000) {
+            next();
+        }
+    "#),
+];
+
+fn compile(source: &str, vm: &VM) -> Chunk {
+    let mut lexer = Lexer::new(source);
+    let mut tokenizer = Tokenizer::new(&mut lexer);
+    let mut parser = Parser::new(&mut tokenizer).expect("benchmark source should lex");
+    let ast = parser.parse().expect("benchmark source should parse");
+    Compiler::with_globals(vm.global_names.clone()).compile(&ast).expect("benchmark source should compile")
+}
+
+fn main() {
+    let save = std::env::args().any(|arg| arg == "--save-baseline");
+    let baseline = load_baseline();
+    let mut medians = Vec::new();
+    
+    println!("{:<16} {:>12} {:>12} {:>12} {:>8}", "workload", "median", "fastest", "baseline", "speedup");
+    for (name, source) in WORKLOADS {
+        let mut times: Vec<Duration> = (0..RUNS).map(|_| {
+            let mut vm = VM::new();
+            let chunk = compile(source, &vm);
+            let start = Instant::now();
+            vm.run(chunk).expect("benchmark script should run");
+            start.elapsed()
+        }).collect();
+        times.sort();
+        let median = ms(times[RUNS / 2]);
+        match baseline.get(*name) {
+            Some(base) => println!(
+                "{:<16} {:>10.2}ms {:>10.2}ms {:>10.2}ms {:>7.2}x",
+                name, median, ms(times[0]), base, base / median
+            ),
+            None => println!("{:<16} {:>10.2}ms {:>10.2}ms {:>12} {:>8}", name, median, ms(times[0]), "-", "-"),
+        }
+        medians.push((name, median));
+    }
+    
+    if save {
+        let mut text = String::from("# median run times in ms that cargo bench compares against\n");
+        for (name, median) in medians {
+            text += &format!("{} {:.2}\n", name, median);
+        }
+        std::fs::write(BASELINE, text).expect("baseline should be writable");
+        println!("saved as the baseline in {}", BASELINE);
+    }
+}
+
+// workload name -> median in ms. no file means nothing to compare against.
+fn load_baseline() -> HashMap<String, f64> {
+    let text = std::fs::read_to_string(BASELINE).unwrap_or_default();
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, median) = line.split_once(' ')?;
+            Some((name.to_string(), median.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,53 @@
+// using sage as a scripting layer from rust.
+// run with: cargo run --example embedding
+use std::collections::HashMap;
+use sagelang::{Engine, Value};
+
+fn main() {
+    let mut engine = Engine::new();
+    
+    // hand the script some configuration
+    engine.set_global("retries", 3);
+    engine.set_global("hosts", vec!["alpha", "beta"]);
+    engine.register_fn("log", Some(1), |_, args| {
+        println!("[script] {}", args[0]);
+        Ok(Value::Null)
+    });
+    
+    let source = r#"
+        let timeout = retries * 10;
+        let limits = {"cpu": 2, "memory": 512};
+        function backoff(attempt) {
+            return attempt * attempt * 100;
+        }
+        log("configured " + str(len(hosts)) + " hosts");
+        timeout + 5;
+    "#;
+    
+    let result = match engine.eval(source) {
+        Ok(value) => value,
+        Err(errs) => {
+            for e in &errs {
+                sagelang::error::errormsg::report(e, "<config>", source);
+            }
+            return;
+        }
+    };
+    println!("last value: {}", f64::try_from(result).unwrap());
+    
+    // read values back out, converting them to rust types
+    let timeout: i64 = engine.get_global("timeout").unwrap().try_into().unwrap();
+    let limits: HashMap<String, f64> = engine.get_global("limits").unwrap().try_into().unwrap();
+    println!("timeout = {}, memory limit = {}", timeout, limits["memory"]);
+    
+    // and call script functions
+    for attempt in 1..=3 {
+        let delay = engine.call("backoff", vec![attempt.into()]).unwrap();
+        println!("attempt {} waits {}ms", attempt, f64::try_from(delay).unwrap());
+    }
+    
+    // script errors come back as values rather than ending the process
+    if let Err(e) = engine.call("backoff", vec![]) {
+        println!("error: {}", e);
+    }
+}
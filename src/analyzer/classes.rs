@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use crate::parser::ast::{AccessModifier, Field, Method};
+
+// what the analyzer knows about a class: the members it declares itself,
+// and the name of its superclass
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    pub superclass: Option<String>,
+    pub fields: HashMap<String, AccessModifier>,
+    pub methods: HashMap<String, AccessModifier>,
+    pub static_fields: HashMap<String, AccessModifier>,
+    pub static_methods: HashMap<String, AccessModifier>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberKind {
+    Field,
+    Method,
+    StaticField,
+    StaticMethod,
+}
+
+impl MemberKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            MemberKind::Field => "field",
+            MemberKind::Method => "method",
+            MemberKind::StaticField => "static field",
+            MemberKind::StaticMethod => "static method",
+        }
+    }
+    
+    pub fn is_method(&self) -> bool {
+        matches!(self, MemberKind::Method | MemberKind::StaticMethod)
+    }
+}
+
+// a member found on a class, and the class that declares it
+pub struct Member<'a> {
+    pub class: &'a str,
+    pub kind: MemberKind,
+    pub access: &'a AccessModifier,
+}
+
+pub enum Lookup<'a> {
+    Found(Member<'a>),
+    Missing,
+    // some ancestor isn't known here, e.g. it was defined by an earlier
+    // eval on another vm, so nothing can be said about the member
+    Unknown,
+}
+
+impl ClassInfo {
+    pub fn new(superclass: &Option<String>, fields: &[Field], methods: &[Method]) -> Self {
+        let fields_where = |is_static: bool| fields.iter()
+            .filter(|field| field.is_static == is_static)
+            .map(|field| (field.name.clone(), field.access.clone()))
+            .collect();
+        let methods_where = |is_static: bool| methods.iter()
+            .filter(|method| method.is_static == is_static)
+            .map(|method| (method.name.clone(), method.access.clone()))
+            .collect();
+        ClassInfo {
+            superclass: superclass.clone(),
+            fields: fields_where(false),
+            methods: methods_where(false),
+            static_fields: fields_where(true),
+            static_methods: methods_where(true),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClassTable {
+    classes: HashMap<String, ClassInfo>,
+}
+
+impl ClassTable {
+    pub fn define(&mut self, name: &str, info: ClassInfo) {
+        self.classes.insert(name.to_string(), info);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.classes.contains_key(name)
+    }
+
+    // the class and its ancestors, nearest first. None if one of them isn't
+    // known. a cycle (only possible through redefinition) also counts as
+    // unknown.
+    fn lineage<'a>(&'a self, name: &'a str) -> Option<Vec<(&'a str, &'a ClassInfo)>> {
+        let mut lineage: Vec<(&str, &ClassInfo)> = Vec::new();
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if lineage.iter().any(|(seen, _)| *seen == name) {
+                return None;
+            }
+            let info = self.classes.get(name)?;
+            lineage.push((name, info));
+            next = info.superclass.as_deref();
+        }
+        Some(lineage)
+    }
+
+    // find an instance field or method by name, the nearest declaration winning
+    pub fn lookup<'a>(&'a self, class: &'a str, name: &str) -> Lookup<'a> {
+        self.find(class, name, |info| [(MemberKind::Field, &info.fields), (MemberKind::Method, &info.methods)])
+    }
+
+    // the same for static fields and methods, used through the class itself
+    pub fn lookup_static<'a>(&'a self, class: &'a str, name: &str) -> Lookup<'a> {
+        self.find(class, name, |info| {
+            [(MemberKind::StaticField, &info.static_fields), (MemberKind::StaticMethod, &info.static_methods)]
+        })
+    }
+
+    fn find<'a>(
+        &'a self,
+        class: &'a str,
+        name: &str,
+        tables: impl Fn(&'a ClassInfo) -> [(MemberKind, &'a HashMap<String, AccessModifier>); 2],
+    ) -> Lookup<'a> {
+        let Some(lineage) = self.lineage(class) else {
+            return Lookup::Unknown;
+        };
+        for (class, info) in lineage {
+            for (kind, table) in tables(info) {
+                if let Some(access) = table.get(name) {
+                    return Lookup::Found(Member { class, kind, access });
+                }
+            }
+        }
+        Lookup::Missing
+    }
+
+    // whether a subclass of `class` declares an instance member by this name.
+    // `this` can be one of its instances, so a method of `class` can call it.
+    pub fn declared_by_subclass(&self, class: &str, name: &str) -> bool {
+        self.classes.iter().any(|(subclass, info)| {
+            subclass != class
+                && (info.fields.contains_key(name) || info.methods.contains_key(name))
+                && self.lineage(subclass).is_some_and(|lineage| lineage.iter().any(|(ancestor, _)| *ancestor == class))
+        })
+    }
+
+    // whether `class` is `ancestor` or inherits from it. unknown ancestry is
+    // given the benefit of the doubt.
+    pub fn inherits(&self, class: &str, ancestor: &str) -> bool {
+        match self.lineage(class) {
+            Some(lineage) => lineage.iter().any(|(name, _)| *name == ancestor),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ * checks that run between parsing and compiling, so mistakes with classes
+ * are caught before any code runs:
+ *   - assigning to a field the class doesn't declare
+ *   - using a private or protected member from outside where it's visible
+ *   - calling a method on `this` that the class doesn't have
+ * the class of a receiver is only known for `this`, `new Foo()`, a class
+ * used by name (for its static members) and variables that are initialized
+ * with an instance and never reassigned; anything else is left to the vm's
+ * checks at runtime.
+ */
+mod classes;
+
+use std::collections::{HashMap, HashSet};
+use crate::parser::ast::{Expr, Stmt, Param, AccessModifier};
+use crate::error::{errormsg, SageError, Span};
+use classes::{ClassTable, ClassInfo, Lookup, Member};
+
+#[derive(Default)]
+pub struct Analyzer {
+    classes: ClassTable,                            // every class seen so far, kept between checks
+    errors: Vec<SageError>,
+    scopes: Vec<HashMap<String, Option<String>>>,   // variables in scope and the class they hold, if known
+    reassigned: HashSet<String>,                    // names assigned to after being declared
+    current_class: Option<String>,
+    in_static: bool,
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Analyzer::default()
+    }
+
+    // check a program, reporting every problem found. the classes it declares
+    // are remembered for later checks unless it has errors.
+    pub fn check(&mut self, stmts: &[Stmt]) -> Result<(), Vec<SageError>> {
+        let known_classes = self.classes.clone();
+        self.reassigned.clear();
+        // classes can be used above their declaration, e.g. in a function
+        self.collect(stmts);
+
+        self.scopes = vec![HashMap::new()];
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+        self.scopes.clear();
+
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        self.classes = known_classes;
+        Err(std::mem::take(&mut self.errors))
+    }
+
+    // record the classes declared anywhere in the program and the variables
+    // that get reassigned
+    fn collect(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Class { name, superclass, fields, methods, .. } => {
+                    self.classes.define(name, ClassInfo::new(superclass, fields, methods));
+                }
+                Stmt::Assign { name, .. } => {
+                    self.reassigned.insert(name.clone());
+                }
+                _ => {}
+            }
+            let (exprs, bodies) = parts(stmt);
+            for expr in exprs {
+                self.collect_expr(expr);
+            }
+            for body in bodies {
+                self.collect(body);
+            }
+        }
+    }
+
+    fn collect_expr(&mut self, expr: &Expr) {
+        if let Expr::Lambda { body, .. } = expr {
+            self.collect(body);
+        }
+        for child in children(expr) {
+            self.collect_expr(child);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::VarDecl { name, value, .. } => {
+                self.visit_expr(value);
+                let class = self.class_of(value);
+                self.declare(name, class);
+            }
+            Stmt::Assign { value, .. } => self.visit_expr(value),
+            Stmt::While { condition, body, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(body, &[]);
+            }
+            Stmt::For { var, iterable, body, .. } => {
+                self.visit_expr(iterable);
+                self.visit_block(body, std::slice::from_ref(var));
+            }
+            Stmt::Function { name, params, body, .. } => {
+                self.declare(name, None);
+                self.visit_function(params, body);
+            }
+            Stmt::If { condition, then_branch, else_branch, elseif_branches, .. } => {
+                self.visit_expr(condition);
+                self.visit_block(then_branch, &[]);
+                for (condition, branch) in elseif_branches {
+                    self.visit_expr(condition);
+                    self.visit_block(branch, &[]);
+                }
+                if let Some(branch) = else_branch {
+                    self.visit_block(branch, &[]);
+                }
+            }
+            Stmt::Print { expr, .. } | Stmt::ExprStmt { expr, .. } => self.visit_expr(expr),
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Block { stmts, .. } => self.visit_block(stmts, &[]),
+            Stmt::Class { name, fields, methods, .. } => {
+                let enclosing_class = self.current_class.replace(name.clone());
+                let enclosing_static = self.in_static;
+                // instance field initializers run as part of making an
+                // instance, static ones when the class is defined
+                for field in fields {
+                    if let Some(value) = &field.value {
+                        self.in_static = field.is_static;
+                        self.visit_expr(value);
+                    }
+                }
+                for method in methods {
+                    self.in_static = method.is_static;
+                    self.visit_function(&method.params, &method.body);
+                }
+                self.current_class = enclosing_class;
+                self.in_static = enclosing_static;
+            }
+        }
+    }
+
+    // a scope holding `names` (of unknown class) and the block's own variables
+    fn visit_block(&mut self, stmts: &[Stmt], names: &[String]) {
+        self.scopes.push(names.iter().map(|name| (name.clone(), None)).collect());
+        for stmt in stmts {
+            self.visit_stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_function(&mut self, params: &[Param], body: &[Stmt]) {
+        let names: Vec<String> = params.iter().map(|param| param.param_name.clone()).collect();
+        self.visit_block(body, &names);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Get { object, name, span } => {
+                self.visit_expr(object);
+                self.check_get(object, name, *span);
+            }
+            Expr::Set { object, name, value, span } => {
+                self.visit_expr(object);
+                self.visit_expr(value);
+                self.check_set(object, name, *span);
+            }
+            Expr::Call { callee, args, span } => {
+                self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+                match &**callee {
+                    Expr::Get { object, name, .. } if matches!(**object, Expr::This { .. }) => {
+                        self.check_method_on_this(name, *span);
+                    }
+                    _ => {}
+                }
+            }
+            Expr::Lambda { params, body, .. } => self.visit_function(params, body),
+            _ => {
+                for child in children(expr) {
+                    self.visit_expr(child);
+                }
+            }
+        }
+    }
+
+    fn check_get(&mut self, object: &Expr, name: &str, span: Span) {
+        let Some((class, is_static)) = self.receiver(object) else {
+            return;
+        };
+        let message = match self.lookup(&class, name, is_static) {
+            Lookup::Found(member) => self.access_error(&member, "access", name),
+            Lookup::Missing | Lookup::Unknown => None,
+        };
+        self.report(message, span);
+    }
+
+    fn check_set(&mut self, object: &Expr, name: &str, span: Span) {
+        // the compiler reports assigning to `class`
+        if name == "class" {
+            return;
+        }
+        let Some((class, is_static)) = self.receiver(object) else {
+            return;
+        };
+        let message = match self.lookup(&class, name, is_static) {
+            Lookup::Found(member) if member.kind.is_method() => {
+                Some(format!("Cannot assign to method '{}' of class '{}'", name, member.class))
+            }
+            Lookup::Found(member) => self.access_error(&member, "set", name),
+            Lookup::Missing => Some(format!(
+                "Cannot assign to undeclared {} '{}' of class '{}'; declare it in the class body",
+                if is_static { "static field" } else { "field" }, name, class
+            )),
+            Lookup::Unknown => None,
+        };
+        self.report(message, span);
+    }
+
+    // a field can hold a function, so calling one is fine too. so is a
+    // method only a subclass defines, as `this` may be one of its instances.
+    fn check_method_on_this(&mut self, name: &str, span: Span) {
+        let Some(class) = self.current_class.clone().filter(|_| !self.in_static) else {
+            return;
+        };
+        let missing = matches!(self.classes.lookup(&class, name), Lookup::Missing);
+        if missing && !self.classes.declared_by_subclass(&class, name) {
+            self.report(Some(format!("Undefined method '{}' on class '{}'", name, class)), span);
+        }
+    }
+
+    // why the code being checked can't use `member`, if it can't. private
+    // members are visible in the class that declares them, protected ones
+    // anywhere in its hierarchy.
+    fn access_error(&self, member: &Member, verb: &str, name: &str) -> Option<String> {
+        let current = self.current_class.as_deref();
+        match member.access {
+            AccessModifier::Public => None,
+            AccessModifier::Private if current == Some(member.class) => None,
+            AccessModifier::Private => Some(format!(
+                "Cannot {} private {} '{}' of class '{}' from outside the class",
+                verb, member.kind.describe(), name, member.class
+            )),
+            AccessModifier::Protected => {
+                let in_hierarchy = current.is_some_and(|current| {
+                    self.classes.inherits(current, member.class) || self.classes.inherits(member.class, current)
+                });
+                if in_hierarchy {
+                    return None;
+                }
+                Some(format!(
+                    "Cannot {} protected {} '{}' of class '{}' from outside its class hierarchy",
+                    verb, member.kind.describe(), name, member.class
+                ))
+            }
+        }
+    }
+
+    fn report(&mut self, message: Option<String>, span: Span) {
+        if let Some(message) = message {
+            self.errors.push(errormsg::compile_error(&message, span));
+        }
+    }
+
+    fn declare(&mut self, name: &str, class: Option<String>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), class);
+        }
+    }
+
+    // the class whose members `object.name` refers to, and whether they're
+    // its static ones: an instance's class, or a class used by name
+    fn receiver(&self, object: &Expr) -> Option<(String, bool)> {
+        if let Some(class) = self.class_of(object) {
+            return Some((class, false));
+        }
+        match object {
+            Expr::Identifier { name, .. } if self.classes.contains(name) && !self.is_variable(name) => {
+                Some((name.clone(), true))
+            }
+            _ => None,
+        }
+    }
+
+    fn lookup<'a>(&'a self, class: &'a str, name: &str, is_static: bool) -> Lookup<'a> {
+        if is_static {
+            self.classes.lookup_static(class, name)
+        } else {
+            self.classes.lookup(class, name)
+        }
+    }
+
+    fn is_variable(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+    }
+
+    // the class of the instance an expression evaluates to, when that's
+    // certain
+    fn class_of(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::This { .. } if !self.in_static => self.current_class.clone(),
+            Expr::New { class, .. } => match &**class {
+                Expr::Identifier { name, .. } if self.classes.contains(name) => Some(name.clone()),
+                _ => None,
+            },
+            Expr::Identifier { name, .. } if !self.reassigned.contains(name) => {
+                self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned().flatten()
+            }
+            Expr::Grouping { expr, .. } => self.class_of(expr),
+            _ => None,
+        }
+    }
+}
+
+// the expressions directly inside an expression. lambda bodies are
+// statements, so they aren't included.
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::List { items, .. } => items.iter().collect(),
+        Expr::Map { entries, .. } => entries.iter().flat_map(|(key, value)| [key, value]).collect(),
+        Expr::UnaryOp { right, .. } => vec![right],
+        Expr::BinaryOp { left, right, .. } => vec![left, right],
+        Expr::Grouping { expr, .. } => vec![expr],
+        Expr::Call { callee, args, .. } | Expr::New { class: callee, args, .. } => {
+            std::iter::once(&**callee).chain(args).collect()
+        }
+        Expr::Get { object, .. } => vec![object],
+        Expr::Set { object, value, .. } => vec![object, value],
+        Expr::Index { object, index, .. } => vec![object, index],
+        Expr::SetIndex { object, index, value, .. } => vec![object, index, value],
+        Expr::Number { .. } | Expr::Identifier { .. } | Expr::StringLit { .. } | Expr::Bool { .. }
+        | Expr::This { .. } | Expr::Super { .. } | Expr::Lambda { .. } => Vec::new(),
+    }
+}
+
+// the expressions and statement lists directly inside a statement
+fn parts(stmt: &Stmt) -> (Vec<&Expr>, Vec<&[Stmt]>) {
+    match stmt {
+        Stmt::VarDecl { value, .. } | Stmt::Assign { value, .. } => (vec![value], Vec::new()),
+        Stmt::While { condition, body, .. } => (vec![condition], vec![body]),
+        Stmt::For { iterable, body, .. } => (vec![iterable], vec![body]),
+        Stmt::Function { body, .. } => (Vec::new(), vec![body]),
+        Stmt::If { condition, then_branch, else_branch, elseif_branches, .. } => {
+            let mut exprs = vec![condition];
+            let mut bodies = vec![then_branch.as_slice()];
+            for (condition, branch) in elseif_branches {
+                exprs.push(condition);
+                bodies.push(branch);
+            }
+            bodies.extend(else_branch.as_deref());
+            (exprs, bodies)
+        }
+        Stmt::Print { expr, .. } | Stmt::ExprStmt { expr, .. } => (vec![expr], Vec::new()),
+        Stmt::Return { value, .. } => (value.iter().collect(), Vec::new()),
+        Stmt::Break { .. } | Stmt::Continue { .. } => (Vec::new(), Vec::new()),
+        Stmt::Block { stmts, .. } => (Vec::new(), vec![stmts]),
+        Stmt::Class { fields, methods, .. } => (
+            fields.iter().filter_map(|field| field.value.as_ref()).collect(),
+            methods.iter().map(|method| method.body.as_slice()).collect(),
+        ),
+    }
+}
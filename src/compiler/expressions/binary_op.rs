@@ -1,26 +1,25 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::lexer::tokens::Token;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_binary_op(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<(), String> {
-        // handle and/or
+    pub fn compile_binary_op(&mut self, left: &Expr, op: &Token, right: &Expr) -> CompileResult {
+        // handle and/or. the jump leaves the left value on the stack as the
+        // result when it short-circuits, otherwise it's popped for the right
         match op {
             Token::And => {
                 self.compile_expr(left)?;
-                self.chunk.write(OpCode::Dup, self.current_line);
                 let end_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-                self.chunk.write(OpCode::Pop, self.current_line);
+                self.chunk.write(OpCode::Pop, self.current_span);
                 self.compile_expr(right)?;
                 self.patch_jump(end_jump);
                 return Ok(());
             }
             Token::Or => {
                 self.compile_expr(left)?;
-                self.chunk.write(OpCode::Dup, self.current_line);
                 let end_jump = self.emit_jump(OpCode::JumpIfTrue(0));
-                self.chunk.write(OpCode::Pop, self.current_line);
+                self.chunk.write(OpCode::Pop, self.current_span);
                 self.compile_expr(right)?;
                 self.patch_jump(end_jump);
                 return Ok(());
@@ -33,19 +32,20 @@ impl Compiler {
         self.compile_expr(right)?;
         
         match op {
-            Token::Plus => self.chunk.write(OpCode::Add, self.current_line),
-            Token::Minus => self.chunk.write(OpCode::Subtract, self.current_line),
-            Token::Star => self.chunk.write(OpCode::Multiply, self.current_line),
-            Token::Slash => self.chunk.write(OpCode::Divide, self.current_line),
-            Token::Percent => self.chunk.write(OpCode::Modulo, self.current_line),
-            Token::EqEq => self.chunk.write(OpCode::Equal, self.current_line),
-            Token::NotEq => self.chunk.write(OpCode::NotEqual, self.current_line),
-            Token::Greater => self.chunk.write(OpCode::Greater, self.current_line),
-            Token::GreaterEq => self.chunk.write(OpCode::GreaterEqual, self.current_line),
-            Token::Less => self.chunk.write(OpCode::Less, self.current_line),
-            Token::LessEq => self.chunk.write(OpCode::LessEqual, self.current_line),
-            Token::DotDot => self.chunk.write(OpCode::BuildRange, self.current_line),
-            _ => return Err(format!("Unsupported binary operator: {:?}", op)),
+            Token::Plus => self.chunk.write(OpCode::Add, self.current_span),
+            Token::Minus => self.chunk.write(OpCode::Subtract, self.current_span),
+            Token::Star => self.chunk.write(OpCode::Multiply, self.current_span),
+            Token::Slash => self.chunk.write(OpCode::Divide, self.current_span),
+            Token::Percent => self.chunk.write(OpCode::Modulo, self.current_span),
+            Token::EqEq => self.chunk.write(OpCode::Equal, self.current_span),
+            Token::NotEq => self.chunk.write(OpCode::NotEqual, self.current_span),
+            Token::Greater => self.chunk.write(OpCode::Greater, self.current_span),
+            Token::GreaterEq => self.chunk.write(OpCode::GreaterEqual, self.current_span),
+            Token::Less => self.chunk.write(OpCode::Less, self.current_span),
+            Token::LessEq => self.chunk.write(OpCode::LessEqual, self.current_span),
+            Token::DotDot => self.chunk.write(OpCode::BuildRange, self.current_span),
+            Token::InstanceOfKw => self.chunk.write(OpCode::InstanceOf, self.current_span),
+            _ => return Err(self.error(&format!("Unsupported binary operator: {:?}", op))),
         }
         
         Ok(())
@@ -1,13 +1,13 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 
 impl Compiler {
-    pub fn compile_bool(&mut self, b: bool) -> Result<(), String> {
+    pub fn compile_bool(&mut self, b: bool) -> CompileResult {
         // compile bools
         if b {
-            self.chunk.write(OpCode::LoadTrue, self.current_line);
+            self.chunk.write(OpCode::LoadTrue, self.current_span);
         } else {
-            self.chunk.write(OpCode::LoadFalse, self.current_line);
+            self.chunk.write(OpCode::LoadFalse, self.current_span);
         }
         Ok(())
     }
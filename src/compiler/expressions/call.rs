@@ -1,9 +1,21 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
+use crate::interpreter::Value;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_call(&mut self, callee: &Expr, args: &[Expr]) -> Result<(), String> {
+    pub fn compile_call(&mut self, callee: &Expr, args: &[Expr]) -> CompileResult {
+        // method calls look the method up on the receiver when they're made
+        if let Expr::Get { object, name, .. } = callee {
+            self.compile_expr(object)?;
+            for arg in args {
+                self.compile_expr(arg)?;
+            }
+            let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
+            self.chunk.write(OpCode::Invoke(name_idx, args.len()), self.current_span);
+            return Ok(());
+        }
+        
         // compile the function expression
         self.compile_expr(callee)?;
         
@@ -13,7 +25,7 @@ impl Compiler {
         }
         
         // call with argument count
-        self.chunk.write(OpCode::Call(args.len()), self.current_line);
+        self.chunk.write(OpCode::Call(args.len()), self.current_span);
         
         Ok(())
     }
@@ -1,16 +1,16 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::interpreter::Value;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_get(&mut self, object: &Expr, name: &str) -> Result<(), String> {
+    pub fn compile_get(&mut self, object: &Expr, name: &str) -> CompileResult {
         // compile the object expression
         self.compile_expr(object)?;
 
         // get the property
         let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-        self.chunk.write(OpCode::GetProperty(name_idx), self.current_line);
+        self.chunk.write(OpCode::GetProperty(name_idx), self.current_span);
         
         Ok(())
     }
@@ -1,8 +1,8 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_grouping(&mut self, expr: &Expr) -> Result<(), String> {
+    pub fn compile_grouping(&mut self, expr: &Expr) -> CompileResult {
         // compile the inner expression
         self.compile_expr(expr)
     }
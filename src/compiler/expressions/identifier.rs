@@ -1,16 +1,18 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
-use crate::interpreter::Value;
 
 impl Compiler {
-    pub fn compile_identifier(&mut self, name: &str) -> Result<(), String> {
+    pub fn compile_identifier(&mut self, name: &str) -> CompileResult {
         // check if it's a local variable
-        if let Some(&idx) = self.locals.get(name) {
-            self.chunk.write(OpCode::GetLocal(idx), self.current_line);
+        if let Some(idx) = self.resolve_local(name) {
+            self.chunk.write(OpCode::GetLocal(idx), self.current_span);
+        } else if let Some(idx) = self.resolve_upvalue(name) {
+            // it's captured from an enclosing function
+            self.chunk.write(OpCode::GetUpvalue(idx), self.current_span);
         } else {
             // it's a global variable
-            let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-            self.chunk.write(OpCode::GetGlobal(name_idx), self.current_line);
+            let slot = self.global_slot(name);
+            self.chunk.write(OpCode::GetGlobal(slot), self.current_span);
         }
         Ok(())
     }
@@ -0,0 +1,20 @@
+use crate::compiler::{Compiler, CompileResult};
+use crate::vm::OpCode;
+use crate::parser::ast::Expr;
+
+impl Compiler {
+    pub fn compile_index(&mut self, object: &Expr, index: &Expr) -> CompileResult {
+        self.compile_expr(object)?;
+        self.compile_expr(index)?;
+        self.chunk.write(OpCode::GetIndex, self.current_span);
+        Ok(())
+    }
+    
+    pub fn compile_set_index(&mut self, object: &Expr, index: &Expr, value: &Expr) -> CompileResult {
+        self.compile_expr(object)?;
+        self.compile_expr(index)?;
+        self.compile_expr(value)?;
+        self.chunk.write(OpCode::SetIndex, self.current_span);
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+use crate::compiler::{Compiler, CompileResult};
+use crate::parser::ast::{Param, Stmt};
+
+impl Compiler {
+    pub fn compile_lambda(&mut self, params: &[Param], body: &[Stmt]) -> CompileResult {
+        // an anonymous function is compiled like a declared one,
+        // but its value is left on the stack instead of stored in a variable
+        self.compile_function("<anonymous>", params, body)
+    }
+}
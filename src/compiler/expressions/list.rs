@@ -1,15 +1,26 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_list(&mut self, items: &[Expr]) -> Result<(), String> {
+    pub fn compile_list(&mut self, items: &[Expr]) -> CompileResult {
         // compile each item
         for item in items {
             self.compile_expr(item)?;
         }
         // create list from stack items
-        self.chunk.write(OpCode::MakeList(items.len()), self.current_line);
+        self.chunk.write(OpCode::MakeList(items.len()), self.current_span);
+        Ok(())
+    }
+    
+    pub fn compile_map(&mut self, entries: &[(Expr, Expr)]) -> CompileResult {
+        // compile each key followed by its value
+        for (key, value) in entries {
+            self.compile_expr(key)?;
+            self.compile_expr(value)?;
+        }
+        // create map from stack pairs
+        self.chunk.write(OpCode::MakeMap(entries.len()), self.current_span);
         Ok(())
     }
 }
@@ -9,5 +9,8 @@ mod grouping;
 mod call;
 mod get;
 mod set;
+mod index;
 mod this;
 mod super_expr;
+mod new_expr;
+mod lambda;
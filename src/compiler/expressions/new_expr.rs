@@ -0,0 +1,18 @@
+use crate::compiler::{Compiler, CompileResult};
+use crate::vm::OpCode;
+use crate::parser::ast::Expr;
+
+impl Compiler {
+    pub fn compile_new(&mut self, class: &Expr, args: &[Expr]) -> CompileResult {
+        // the class, then the constructor's arguments
+        self.compile_expr(class)?;
+        for arg in args {
+            self.compile_expr(arg)?;
+        }
+        
+        // create the instance and run its constructor
+        self.chunk.write(OpCode::New(args.len()), self.current_span);
+        
+        Ok(())
+    }
+}
@@ -1,11 +1,11 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::interpreter::Value;
 
 impl Compiler {
-    pub fn compile_number(&mut self, n: f64) -> Result<(), String> {
+    pub fn compile_number(&mut self, n: f64) -> CompileResult {
         let idx = self.chunk.add_constant(Value::Number(n));
-        self.chunk.write(OpCode::LoadConst(idx), self.current_line);
+        self.chunk.write(OpCode::LoadConst(idx), self.current_span);
         Ok(())
     }
 }
@@ -1,10 +1,15 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::interpreter::Value;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_set(&mut self, object: &Expr, name: &str, value: &Expr) -> Result<(), String> {
+    pub fn compile_set(&mut self, object: &Expr, name: &str, value: &Expr) -> CompileResult {
+        // `obj.class` is read-only: an instance's class can't change
+        if name == "class" {
+            return Err(self.error("Cannot assign to 'class'"));
+        }
+        
         // compile the object expression
         self.compile_expr(object)?;
 
@@ -13,7 +18,7 @@ impl Compiler {
 
         // set the property
         let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-        self.chunk.write(OpCode::SetProperty(name_idx), self.current_line);
+        self.chunk.write(OpCode::SetProperty(name_idx), self.current_span);
         
         Ok(())
     }
@@ -1,25 +1,28 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::interpreter::Value;
 
 impl Compiler {
-    pub fn compile_super(&mut self, method: &str) -> Result<(), String> {
+    pub fn compile_super(&mut self, method: &str) -> CompileResult {
         // check if we're in a class context
-        let superclass_name = self.current_superclass.as_ref()
-            .ok_or("Cannot use 'super' outside of a class with a superclass")?;
+        let superclass_name = self.current_superclass.clone()
+            .ok_or_else(|| self.error("Cannot use 'super' outside of a class with a superclass"))?;
+        if method == "constructor" && !self.is_constructor {
+            return Err(self.error("The superclass constructor can only be called from a constructor"));
+        }
 
-        // push 'this' (local 0) onto the stack first
-        self.chunk.write(OpCode::GetLocal(0), self.current_line);
+        // push 'this' onto the stack first
+        self.compile_this()?;
 
         // push the superclass onto the stack second
         // the superclass is stored in globals
-        let superclass_idx = self.chunk.add_constant(Value::String(superclass_name.clone()));
-        self.chunk.write(OpCode::GetGlobal(superclass_idx), self.current_line);
+        let superclass_slot = self.global_slot(&superclass_name);
+        self.chunk.write(OpCode::GetGlobal(superclass_slot), self.current_span);
 
         // get the superclass method
         // stack order: [this, superclass] -> GetSuper pops superclass then this
         let method_idx = self.chunk.add_constant(Value::String(method.to_string()));
-        self.chunk.write(OpCode::GetSuper(method_idx), self.current_line);
+        self.chunk.write(OpCode::GetSuper(method_idx), self.current_span);
         
         Ok(())
     }
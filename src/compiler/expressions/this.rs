@@ -1,10 +1,19 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 
 impl Compiler {
-    pub fn compile_this(&mut self) -> Result<(), String> {
-        // 'this' is always stored as local variable 0 in methods
-        self.chunk.write(OpCode::GetLocal(0), self.current_line);
+    pub fn compile_this(&mut self) -> CompileResult {
+        // 'this' is always stored as local variable 0 in methods,
+        // and closures inside a method capture it like any other local
+        if let Some(idx) = self.resolve_local("this") {
+            self.chunk.write(OpCode::GetLocal(idx), self.current_span);
+        } else if let Some(idx) = self.resolve_upvalue("this") {
+            self.chunk.write(OpCode::GetUpvalue(idx), self.current_span);
+        } else if self.current_class.is_some() {
+            return Err(self.error("Cannot use 'this' in a static method"));
+        } else {
+            return Err(self.error("Cannot use 'this' outside of a class"));
+        }
         Ok(())
     }
 }
@@ -1,14 +1,15 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::lexer::tokens::Token;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_unary_op(&mut self, op: &Token, right: &Expr) -> Result<(), String> {
+    pub fn compile_unary_op(&mut self, op: &Token, right: &Expr) -> CompileResult {
         self.compile_expr(right)?;
         match op {
-            Token::Minus => self.chunk.write(OpCode::Negate, self.current_line),
-            _ => return Err(format!("Unsupported unary operator: {:?}", op)),
+            Token::Minus => self.chunk.write(OpCode::Negate, self.current_span),
+            Token::Bang => self.chunk.write(OpCode::Not, self.current_span),
+            _ => return Err(self.error(&format!("Unsupported unary operator: {:?}", op))),
         }
         Ok(())
     }
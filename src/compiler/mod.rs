@@ -2,43 +2,103 @@ mod statements;
 mod expressions;
 
 use crate::parser::ast::{Expr, Stmt};
-use crate::vm::{Chunk, OpCode};
-use std::collections::HashMap;
+use crate::vm::{Chunk, OpCode, GlobalTable};
+use crate::interpreter::Capture;
+use crate::error::{errormsg, SageError, Span};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+pub type CompileResult<T = ()> = Result<T, SageError>;
 
 pub struct Compiler {
     pub chunk: Chunk,
-    pub locals: HashMap<String, usize>,
-    pub local_count: usize,
+    pub locals: Vec<Local>,                 // locals in scope, in stack slot order
     pub scope_depth: usize,
     pub current_class: Option<String>,
     pub current_superclass: Option<String>,
-    pub current_line: usize,
+    pub is_constructor: bool,               // compiling a class's constructor
+    pub current_span: Span,
+    pub enclosing: Option<Box<Compiler>>,   // compiler of the surrounding function
+    pub upvalues: Vec<Capture>,             // variables this function captures
+    pub loops: Vec<LoopContext>,            // loops enclosing the code being compiled
+    pub globals: Rc<RefCell<GlobalTable>>,  // slots of global variables, shared with the vm
+}
+
+// a local variable. its slot is its position in `locals`, and the value
+// lives in that slot of the function's stack window.
+pub struct Local {
+    pub name: String,
+    pub depth: usize,       // scope depth it was declared at
+    pub is_captured: bool,  // whether an inner function captures it
+}
+
+// a loop being compiled, with the break and continue jumps that still need
+// patching once the loop's end and increment are known
+pub struct LoopContext {
+    pub body_start: usize, // first local slot of the loop body's scope, popped on break and continue
+    pub breaks: Vec<usize>,
+    pub continues: Vec<usize>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
 }
 
 impl Compiler {
     pub fn new() -> Self {
         Compiler {
             chunk: Chunk::new("main".to_string()),
-            locals: HashMap::new(),
-            local_count: 0,
+            locals: Vec::new(),
             scope_depth: 0,
             current_class: None,
             current_superclass: None,
-            current_line: 0,
+            is_constructor: false,
+            current_span: Span::default(),
+            enclosing: None,
+            upvalues: Vec::new(),
+            loops: Vec::new(),
+            globals: Rc::new(RefCell::new(GlobalTable::new())),
         }
     }
     
-    pub fn compile(&mut self, stmts: &[Stmt]) -> Result<Chunk, String> {
+    // a compiler whose globals go in the slots the given table assigns,
+    // usually a vm's so the code can run on it
+    pub fn with_globals(globals: Rc<RefCell<GlobalTable>>) -> Self {
+        Compiler { globals, ..Compiler::new() }
+    }
+    
+    pub fn compile(&mut self, stmts: &[Stmt]) -> CompileResult<Chunk> {
         for stmt in stmts {
             self.compile_stmt(stmt)?;
         }
         
+        self.chunk.write(OpCode::LoadNull, self.current_span);
+        self.chunk.write(OpCode::Return, self.current_span);
         Ok(self.chunk.clone())
     }
     
-    pub fn compile_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
-        self.current_line = stmt.line();
-        match stmt {
+    // compile a line of repl input. if it ends with an expression statement,
+    // that expression's value is returned from the chunk so it can be echoed.
+    pub fn compile_repl(&mut self, stmts: &[Stmt]) -> CompileResult<Chunk> {
+        if let Some((Stmt::ExprStmt { expr, .. }, rest)) = stmts.split_last() {
+            for stmt in rest {
+                self.compile_stmt(stmt)?;
+            }
+            self.compile_expr(expr)?;
+            self.chunk.write(OpCode::Return, self.current_span);
+            return Ok(self.chunk.clone());
+        }
+        
+        self.compile(stmts)
+    }
+    
+    // the span of the statement or expression being compiled is restored once
+    // its children are done, so the instructions it emits point at all of it
+    pub fn compile_stmt(&mut self, stmt: &Stmt) -> CompileResult {
+        let enclosing_span = std::mem::replace(&mut self.current_span, stmt.span());
+        let result = match stmt {
             Stmt::VarDecl { name, value, .. } => self.compile_var_decl(name, value),
             Stmt::Assign { name, value, .. } => self.compile_assign(name, value),
             Stmt::Print { expr, .. } => self.compile_print(expr),
@@ -51,62 +111,127 @@ impl Compiler {
             Stmt::For { var, iterable, body, .. } => self.compile_for_stmt(var, iterable, body),
             Stmt::Function { name, params, body, .. } => self.compile_function_stmt(name, params, body),
             Stmt::Return { value: expr, .. } => self.compile_return_stmt(expr),
+            Stmt::Break { .. } => self.compile_break_stmt(),
+            Stmt::Continue { .. } => self.compile_continue_stmt(),
             Stmt::Class { name, superclass, fields, methods, .. } => self.compile_class_stmt(name, superclass, fields, methods),
-        }
+        };
+        self.current_span = enclosing_span;
+        result
     }
     
-    pub fn compile_expr(&mut self, expr: &Expr) -> Result<(), String> {
-        self.current_line = expr.line();
-        match expr {
+    pub fn compile_expr(&mut self, expr: &Expr) -> CompileResult {
+        let enclosing_span = std::mem::replace(&mut self.current_span, expr.span());
+        let result = match expr {
             Expr::Number { value: n, .. } => self.compile_number(*n),
             Expr::Bool { value: b, .. } => self.compile_bool(*b),
             Expr::StringLit { value: s, .. } => self.compile_string_lit(s),
             Expr::Identifier { name, .. } => self.compile_identifier(name),
             Expr::List { items, .. } => self.compile_list(items),
+            Expr::Map { entries, .. } => self.compile_map(entries),
             Expr::UnaryOp { op, right, .. } => self.compile_unary_op(op, right),
             Expr::BinaryOp { left, op, right, .. } => self.compile_binary_op(left, op, right),
             Expr::Grouping { expr, .. } => self.compile_grouping(expr),
             Expr::Call { callee, args, .. } => self.compile_call(callee, args),
             Expr::Get { object, name, .. } => self.compile_get(object, name),
             Expr::Set { object, name, value, .. } => self.compile_set(object, name, value),
+            Expr::Index { object, index, .. } => self.compile_index(object, index),
+            Expr::SetIndex { object, index, value, .. } => self.compile_set_index(object, index, value),
             Expr::This { .. } => self.compile_this(),
             Expr::Super { method, .. } => self.compile_super(method),
-        }
+            Expr::New { class, args, .. } => self.compile_new(class, args),
+            Expr::Lambda { params, body, .. } => self.compile_lambda(params, body),
+        };
+        self.current_span = enclosing_span;
+        result
     }
     
     fn begin_scope(&mut self) {
         self.scope_depth += 1;
     }
     
+    // pop the scope's locals off the stack and forget them, so their slots
+    // are reused and any outer variables they shadowed are visible again
     fn end_scope(&mut self) {
         self.scope_depth -= 1;
-        
-        let mut to_remove = Vec::new();
-        for (name, &idx) in &self.locals {
-            if idx >= self.local_count {
-                to_remove.push(name.clone());
-            }
+        let start = self.locals.iter()
+            .rposition(|local| local.depth <= self.scope_depth)
+            .map_or(0, |i| i + 1);
+        self.pop_locals(start);
+        self.locals.truncate(start);
+    }
+    
+    // emit the pops for the locals from slot `start` up, without forgetting
+    // them. locals captured by closures are closed over first, since their
+    // slots are about to go away.
+    fn pop_locals(&mut self, start: usize) {
+        if self.locals[start..].iter().any(|local| local.is_captured) {
+            self.chunk.write(OpCode::CloseUpvalues(start), self.current_span);
         }
-        for name in to_remove {
-            self.locals.remove(&name);
+        for _ in start..self.locals.len() {
+            self.chunk.write(OpCode::Pop, self.current_span);
         }
     }
     
+    // add a local in the current scope. its value must be the next thing
+    // pushed onto the stack.
+    fn declare_local(&mut self, name: &str) -> CompileResult<usize> {
+        let redeclared = self.locals.iter().rev()
+            .take_while(|local| local.depth == self.scope_depth)
+            .any(|local| local.name == name);
+        if redeclared {
+            return Err(self.error(&format!("Variable '{}' is already declared in this scope", name)));
+        }
+        self.locals.push(Local { name: name.to_string(), depth: self.scope_depth, is_captured: false });
+        Ok(self.locals.len() - 1)
+    }
+    
+    // the slot of the innermost local with this name
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+    
+    // find a variable in an enclosing function, capturing it (and threading it
+    // through every function in between) as an upvalue of this one
+    fn resolve_upvalue(&mut self, name: &str) -> Option<usize> {
+        let enclosing = self.enclosing.as_mut()?;
+        if let Some(idx) = enclosing.resolve_local(name) {
+            enclosing.locals[idx].is_captured = true;
+            return Some(self.add_upvalue(true, idx));
+        }
+        let idx = enclosing.resolve_upvalue(name)?;
+        Some(self.add_upvalue(false, idx))
+    }
+    
+    fn add_upvalue(&mut self, is_local: bool, index: usize) -> usize {
+        let capture = Capture { is_local, index };
+        if let Some(existing) = self.upvalues.iter().position(|c| *c == capture) {
+            return existing;
+        }
+        self.upvalues.push(capture);
+        self.upvalues.len() - 1
+    }
+    
+    fn global_slot(&self, name: &str) -> usize {
+        self.globals.borrow_mut().intern(name)
+    }
+    
+    fn error(&self, message: &str) -> SageError {
+        errormsg::compile_error(message, self.current_span)
+    }
+    
+    // emit a jump with a placeholder target, returning its offset to patch
     fn emit_jump(&mut self, instruction: OpCode) -> usize {
-        self.chunk.write(instruction, self.current_line);
-        self.chunk.code.len() - 1
+        let offset = self.chunk.code.len();
+        self.chunk.write(instruction, self.current_span);
+        offset
     }
     
     fn patch_jump(&mut self, offset: usize) {
         let jump = self.chunk.code.len();
-        
-        match &mut self.chunk.code[offset] {
-            OpCode::Jump(addr) |
-            OpCode::JumpIfFalse(addr) |
-            OpCode::JumpIfTrue(addr) => {
-                *addr = jump;
-            }
-            _ => {}
-        }
+        self.patch_jump_to(offset, jump);
+    }
+    
+    fn patch_jump_to(&mut self, offset: usize, jump: usize) {
+        self.chunk.patch_jump(offset, jump);
     }
 }
@@ -1,21 +1,28 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
-use crate::interpreter::Value;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_assign(&mut self, name: &str, value: &Expr) -> Result<(), String> {
+    pub fn compile_assign(&mut self, name: &str, value: &Expr) -> CompileResult {
         self.compile_expr(value)?;
-        
+        self.compile_store(name);
+        // assignment is a statement, so nothing is left on the stack
+        self.chunk.write(OpCode::Pop, self.current_span);
+        Ok(())
+    }
+    
+    // store the value on top of the stack in a variable, leaving it there
+    pub fn compile_store(&mut self, name: &str) {
         // check if it's a local variable first
-        if let Some(&idx) = self.locals.get(name) {
-            self.chunk.write(OpCode::SetLocal(idx), self.current_line);
+        if let Some(idx) = self.resolve_local(name) {
+            self.chunk.write(OpCode::SetLocal(idx), self.current_span);
+        } else if let Some(idx) = self.resolve_upvalue(name) {
+            // it's captured from an enclosing function
+            self.chunk.write(OpCode::SetUpvalue(idx), self.current_span);
         } else {
             // it's global
-            let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-            self.chunk.write(OpCode::SetGlobal(name_idx), self.current_line);
+            let slot = self.global_slot(name);
+            self.chunk.write(OpCode::SetGlobal(slot), self.current_span);
         }
-        
-        Ok(())
     }
 }
@@ -1,8 +1,8 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::parser::ast::Stmt;
 
 impl Compiler {
-    pub fn compile_block(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+    pub fn compile_block(&mut self, stmts: &[Stmt]) -> CompileResult {
         // begin a new scope
         self.begin_scope();
 
@@ -1,111 +1,210 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
-use crate::interpreter::Value;
+use crate::error::Span;
+use crate::interpreter::{Value, Function, Class};
 use crate::parser::ast::{Method, Field};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 impl Compiler {
-    pub fn compile_class_stmt(&mut self, name: &str, superclass: &Option<String>, fields: &[Field], methods: &[Method]) -> Result<(), String> {
-        // create the class first (will be stored in globals)
-        let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-        self.chunk.write(OpCode::DefineClass(name_idx), self.current_line);
-        
+    pub fn compile_class_stmt(&mut self, name: &str, superclass: &Option<String>, fields: &[Field], methods: &[Method]) -> CompileResult {
         // compile methods
         let mut instance_method_map = HashMap::new();
         let mut static_method_map = HashMap::new();
+        // methods that capture locals of an enclosing function, which become
+        // closures each time the class is defined
+        let mut closures = Vec::new();
         
         for method in methods {
             // compile method body
-            let mut method_compiler = Compiler::new();
+            let mut method_compiler = Compiler::with_globals(self.globals.clone());
             method_compiler.chunk.name = format!("{}::{}", name, method.name);
             
             // set class context for super keyword
             method_compiler.current_class = Some(name.to_string());
             method_compiler.current_superclass = superclass.clone();
+            method_compiler.is_constructor = !method.is_static && method.name == "constructor";
+            method_compiler.scope_depth = 1;
+
+            method_compiler.current_span = self.current_span;
 
             // set up parameters (including 'this' as local 0 for instance methods)
             if !method.is_static {
-                method_compiler.locals.insert("this".to_string(), 0);
-                method_compiler.local_count = 1;
+                method_compiler.declare_local("this")?;
             }
             
-            for (i, param) in method.params.iter().enumerate() {
-                let local_idx = if method.is_static { i } else { i + 1 };
-                method_compiler.locals.insert(param.param_name.clone(), local_idx);
-                method_compiler.local_count = local_idx + 1;
+            for param in &method.params {
+                method_compiler.declare_local(&param.param_name)?;
             }
             
             // compile method body
-            for stmt in &method.body {
-                method_compiler.compile_stmt(stmt)?;
-            }
+            self.compile_enclosed(&mut method_compiler, |compiler| {
+                method.body.iter().try_for_each(|stmt| compiler.compile_stmt(stmt))
+            })?;
 
             // ensure method returns something
             // for constructors, return 'this'. for regular methods, return null.
             if method.name == "constructor" {
-                method_compiler.chunk.write(OpCode::GetLocal(0), 0); // Get 'this'
+                method_compiler.chunk.write(OpCode::GetLocal(0), Span::default()); // Get 'this'
             } else {
-                method_compiler.chunk.write(OpCode::LoadNull, 0);
+                method_compiler.chunk.write(OpCode::LoadNull, Span::default());
             }
-            method_compiler.chunk.write(OpCode::Return, 0);
+            method_compiler.chunk.write(OpCode::Return, Span::default());
 
             // create method value
-            let method_value = Value::Function {
+            let method_value = Rc::new(Function {
                 name: method.name.clone(),
                 param_count: method.params.len(),
-                chunk: method_compiler.chunk,
-            };
+                captures: std::mem::take(&mut method_compiler.upvalues),
+                chunk: Rc::new(method_compiler.chunk),
+                upvalues: Vec::new(),
+                class_name: Some(name.to_string()),
+            });
+            if !method_value.captures.is_empty() {
+                closures.push((method.name.clone(), method.is_static, method_value.clone()));
+            }
 
             // store in appropriate map based on static flag
             if method.is_static {
-                static_method_map.insert(method.name.clone(), method_value);
+                static_method_map.insert(method.name.clone(), Value::Function(method_value));
             } else {
-                instance_method_map.insert(method.name.clone(), method_value);
+                instance_method_map.insert(method.name.clone(), Value::Function(method_value));
+            }
+        }
+
+        let (static_fields, instance_fields): (Vec<&Field>, Vec<&Field>) = fields.iter().partition(|field| field.is_static);
+        for (is_static, fields) in [(false, &instance_fields), (true, &static_fields)] {
+            if fields.is_empty() {
+                continue;
             }
+            let initializer = self.compile_field_initializer(name, superclass, fields, is_static)?;
+            if !initializer.captures.is_empty() {
+                closures.push((initializer.name.clone(), is_static, initializer.clone()));
+            }
+            let map = if is_static { &mut static_method_map } else { &mut instance_method_map };
+            map.insert(initializer.name.clone(), Value::Function(initializer));
         }
 
         // build field and method access maps
         let mut field_access_map = HashMap::new();
-        for field in fields {
+        for field in &instance_fields {
             field_access_map.insert(field.name.clone(), field.access.clone());
         }
         
+        let mut static_field_access_map = HashMap::new();
+        for field in &static_fields {
+            static_field_access_map.insert(field.name.clone(), field.access.clone());
+        }
+        
         let mut method_access_map = HashMap::new();
         for method in methods {
             method_access_map.insert(method.name.clone(), method.access.clone());
         }
 
-        // store class with methods as a constant
-        let class_value = Value::Class {
-            name: name.to_string(),
-            superclass: None,
-            field_access: field_access_map.clone(),
-            method_access: method_access_map.clone(),
-            methods: instance_method_map,
-            static_methods: static_method_map,
-        };
+        // store class with methods as a constant. with a superclass this is
+        // a template that Inherit makes the real class from.
+        let class_value = Value::Class(Rc::new(Class::new(
+            name.to_string(),
+            None,
+            field_access_map,
+            method_access_map,
+            instance_method_map,
+            static_method_map,
+            static_field_access_map,
+        )));
         
         let class_idx = self.chunk.add_constant(class_value);
-        self.chunk.write(OpCode::LoadConst(class_idx), self.current_line);
-
-        // store class in global variable
-        let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-
-        self.chunk.write(OpCode::SetGlobal(name_idx), self.current_line);
+        self.chunk.write(OpCode::LoadConst(class_idx), self.current_span);
 
         // handle inheritance if there's a superclass
         if let Some(super_name) = superclass {
-            // load superclass
-            self.compile_identifier(super_name)?;
+            if super_name == name {
+                return Err(self.error(&format!("Class '{}' cannot inherit from itself", name)));
+            }
             
-            // load subclass name
-            let class_name_const = self.chunk.add_constant(Value::String(name.to_string()));
-            self.chunk.write(OpCode::LoadConst(class_name_const), self.current_line);
+            // load superclass and link the two
+            self.compile_identifier(super_name)?;
+            self.chunk.write(OpCode::Inherit, self.current_span);
+        }
+        
+        for (method_name, is_static, method) in closures {
+            let method_idx = self.chunk.add_constant(Value::Function(method));
+            self.chunk.write(OpCode::Closure(method_idx), self.current_span);
+            let name_idx = self.chunk.add_constant(Value::String(method_name));
+            self.chunk.write(OpCode::Method(name_idx, is_static as usize), self.current_span);
+        }
 
-            // inherit from superclass
-            self.chunk.write(OpCode::Inherit, self.current_line);
+        // store class in global variable
+        let slot = self.global_slot(name);
+        self.chunk.write(OpCode::SetGlobal(slot), self.current_span);
+        
+        // static fields are initialized once the class can be referred to by
+        // name, so their initializers can use it
+        if !static_fields.is_empty() {
+            let name_idx = self.chunk.add_constant(Value::String(Class::STATIC_INITIALIZER.to_string()));
+            self.chunk.write(OpCode::Invoke(name_idx, 0), self.current_span);
         }
+        self.chunk.write(OpCode::Pop, self.current_span);
         
         Ok(())
     }
+    
+    // a method that sets each declared field to its initializer, or to null
+    // if it has none. the vm runs the instance one on a new instance before
+    // the constructor, and the static one as soon as the class is defined.
+    fn compile_field_initializer(&mut self, name: &str, superclass: &Option<String>, fields: &[&Field], is_static: bool) -> CompileResult<Rc<Function>> {
+        let method_name = if is_static { Class::STATIC_INITIALIZER } else { Class::FIELD_INITIALIZER };
+        let mut compiler = Compiler::with_globals(self.globals.clone());
+        compiler.chunk.name = format!("{}::{}", name, method_name);
+        compiler.current_class = Some(name.to_string());
+        compiler.current_superclass = superclass.clone();
+        compiler.scope_depth = 1;
+        let class_span = self.current_span;
+        compiler.current_span = class_span;
+        if !is_static {
+            compiler.declare_local("this")?;
+        }
+        
+        self.compile_enclosed(&mut compiler, |compiler| {
+            for field in fields {
+                // errors in an initializer point at it rather than the whole class
+                compiler.current_span = field.value.as_ref().map_or(class_span, |value| value.span());
+                if is_static {
+                    compiler.compile_identifier(name)?;
+                } else {
+                    compiler.chunk.write(OpCode::GetLocal(0), compiler.current_span);
+                }
+                match &field.value {
+                    Some(value) => compiler.compile_expr(value)?,
+                    None => compiler.chunk.write(OpCode::LoadNull, compiler.current_span),
+                }
+                let name_idx = compiler.chunk.add_constant(Value::String(field.name.clone()));
+                compiler.chunk.write(OpCode::SetProperty(name_idx), compiler.current_span);
+                compiler.chunk.write(OpCode::Pop, compiler.current_span);
+            }
+            Ok(())
+        })?;
+        compiler.chunk.write(OpCode::LoadNull, Span::default());
+        compiler.chunk.write(OpCode::Return, Span::default());
+        
+        Ok(Rc::new(Function {
+            name: method_name.to_string(),
+            param_count: 0,
+            captures: std::mem::take(&mut compiler.upvalues),
+            chunk: Rc::new(compiler.chunk),
+            upvalues: Vec::new(),
+            class_name: Some(name.to_string()),
+        }))
+    }
+    
+    // compile a member's body with this compiler enclosing it, like a nested
+    // function, so it can capture locals of the function the class is in
+    fn compile_enclosed(&mut self, compiler: &mut Compiler, body: impl FnOnce(&mut Compiler) -> CompileResult) -> CompileResult {
+        compiler.enclosing = Some(Box::new(std::mem::take(self)));
+        let result = body(compiler);
+        if let Some(enclosing) = compiler.enclosing.take() {
+            *self = *enclosing;
+        }
+        result
+    }
 }
@@ -1,12 +1,13 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::interpreter::Value;
 use crate::parser::ast::{Expr, Stmt};
 
 impl Compiler {
-    pub fn compile_for_stmt(&mut self, var: &str, iterable: &Expr, body: &[Stmt]) -> Result<(), String> {
-        // compile iterable
+    pub fn compile_for_stmt(&mut self, var: &str, iterable: &Expr, body: &[Stmt]) -> CompileResult {
+        // compile iterable. maps are walked through a list of their keys
         self.compile_expr(iterable)?;
+        self.chunk.write(OpCode::GetIter, self.current_span);
         
         // we'll compile this as:
         // 1. store list/range in a temp local
@@ -15,58 +16,68 @@ impl Compiler {
         
         self.begin_scope();
         
-        // store iterable
-        let iterable_idx = self.local_count;
-        self.chunk.write(OpCode::SetLocal(iterable_idx), self.current_line);
-        self.local_count += 1;
+        // the iterable and counter are hidden locals. their names can't be
+        // written in a script, so they never clash with the loop's variables.
+        let iterable_idx = self.declare_local("(iterable)")?;
         
         // initialize counter to 0
         let zero_const = self.chunk.add_constant(Value::Number(0.0));
-        self.chunk.write(OpCode::LoadConst(zero_const), self.current_line);
-        let counter_idx = self.local_count;
-        self.chunk.write(OpCode::SetLocal(counter_idx), self.current_line);
-        self.local_count += 1;
+        self.chunk.write(OpCode::LoadConst(zero_const), self.current_span);
+        let counter_idx = self.declare_local("(counter)")?;
         
         // loop start
         let loop_start = self.chunk.code.len();
         
-        // get current item from list
-        self.chunk.write(OpCode::GetLocal(iterable_idx), self.current_line);
-        self.chunk.write(OpCode::GetLocal(counter_idx), self.current_line);
-        self.chunk.write(OpCode::GetIndex, self.current_line);
+        // check counter < length
+        self.chunk.write(OpCode::GetLocal(counter_idx), self.current_span);
+        self.chunk.write(OpCode::GetLocal(iterable_idx), self.current_span);
+        self.chunk.write(OpCode::Len, self.current_span);
+        self.chunk.write(OpCode::Less, self.current_span);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        self.chunk.write(OpCode::Pop, self.current_span);
         
-        // check if we got null (end of list)
-        self.chunk.write(OpCode::Dup, self.current_line);
-        self.chunk.write(OpCode::LoadNull, self.current_line);
-        self.chunk.write(OpCode::Equal, self.current_line);
-        let exit_jump = self.emit_jump(OpCode::JumpIfTrue(0));
-        self.chunk.write(OpCode::Pop, self.current_line);
+        // get current item
+        self.chunk.write(OpCode::GetLocal(iterable_idx), self.current_span);
+        self.chunk.write(OpCode::GetLocal(counter_idx), self.current_span);
+        self.chunk.write(OpCode::GetIndex, self.current_span);
         
-        // store current item in loop variable
-        let var_idx = self.local_count;
-        self.locals.insert(var.to_string(), var_idx);
-        self.chunk.write(OpCode::SetLocal(var_idx), self.current_line);
-        self.local_count += 1;
+        // the loop variable and body get their own scope, closed every
+        // iteration, so closures capture that iteration's value. break and
+        // continue pop the loop variable along with the body's locals.
+        self.begin_scope();
+        self.begin_loop();
+        self.declare_local(var)?;
         
         // compile body
         for stmt in body {
             self.compile_stmt(stmt)?;
         }
+        let loop_ctx = self.end_loop();
+        self.end_scope();
+        
+        // continue still has to move on to the next item
+        for jump in loop_ctx.continues {
+            self.patch_jump(jump);
+        }
         
         // increment counter
-        self.chunk.write(OpCode::GetLocal(counter_idx), self.current_line);
+        self.chunk.write(OpCode::GetLocal(counter_idx), self.current_span);
         let one_const = self.chunk.add_constant(Value::Number(1.0));
-        self.chunk.write(OpCode::LoadConst(one_const), self.current_line);
-        self.chunk.write(OpCode::Add, self.current_line);
-        self.chunk.write(OpCode::SetLocal(counter_idx), self.current_line);
-        self.chunk.write(OpCode::Pop, self.current_line);
+        self.chunk.write(OpCode::LoadConst(one_const), self.current_span);
+        self.chunk.write(OpCode::Add, self.current_span);
+        self.chunk.write(OpCode::SetLocal(counter_idx), self.current_span);
+        self.chunk.write(OpCode::Pop, self.current_span);
         
         // loop back
-        self.chunk.write(OpCode::Loop(loop_start), self.current_line);
+        self.chunk.write(OpCode::Loop(loop_start), self.current_span);
         
         // patch exit
         self.patch_jump(exit_jump);
-        self.chunk.write(OpCode::Pop, self.current_line); // Pop the comparison result
+        self.chunk.write(OpCode::Pop, self.current_span); // Pop the comparison result
+        
+        for jump in loop_ctx.breaks {
+            self.patch_jump(jump);
+        }
         
         self.end_scope();
         
@@ -1,48 +1,76 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
-use crate::interpreter::Value;
+use crate::error::Span;
+use crate::interpreter::{Value, Function};
 use crate::parser::ast::{Param, Stmt};
+use std::rc::Rc;
 
 impl Compiler {
-    pub fn compile_function_stmt(&mut self, name: &str, params: &[Param], body: &[Stmt]) -> Result<(), String> {
-        // compile the function body into a separate chunk
-        let mut func_compiler = Compiler::new();
+    pub fn compile_function_stmt(&mut self, name: &str, params: &[Param], body: &[Stmt]) -> CompileResult {
+        // declare a local function before compiling its body so it can call itself
+        // (the function value is pushed into the slot it's given)
+        let is_local = self.scope_depth > 0;
+        if is_local {
+            self.declare_local(name)?;
+        }
+        
+        self.compile_function(name, params, body)?;
+        
+        if !is_local {
+            let slot = self.global_slot(name);
+            self.chunk.write(OpCode::SetGlobal(slot), self.current_span);
+            self.chunk.write(OpCode::Pop, self.current_span);
+        }
+        
+        Ok(())
+    }
+    
+    // compile a function body into a separate chunk and push the function
+    // onto the stack, as a closure if it captures any variables
+    pub fn compile_function(&mut self, name: &str, params: &[Param], body: &[Stmt]) -> CompileResult {
+        let mut func_compiler = Compiler::with_globals(self.globals.clone());
         func_compiler.chunk.name = name.to_string();
+        func_compiler.current_class = self.current_class.clone();
+        func_compiler.current_superclass = self.current_superclass.clone();
+        // the body is a local scope, so its variables don't leak into globals
+        func_compiler.scope_depth = 1;
+        func_compiler.current_span = self.current_span;
         
         // set up parameters as local variables
-        for (i, param) in params.iter().enumerate() {
-            func_compiler.locals.insert(param.param_name.clone(), i);
-            func_compiler.local_count = i + 1;
+        for param in params {
+            func_compiler.declare_local(&param.param_name)?;
         }
         
-        // compile function body
-        for stmt in body {
-            func_compiler.compile_stmt(stmt)?;
+        // the function's compiler owns this one while it compiles the body,
+        // so it can resolve and capture variables from the enclosing scopes
+        func_compiler.enclosing = Some(Box::new(std::mem::take(self)));
+        let result = body.iter().try_for_each(|stmt| func_compiler.compile_stmt(stmt));
+        if let Some(enclosing) = func_compiler.enclosing.take() {
+            *self = *enclosing;
         }
+        result?;
         
         // ensure function returns something
-        func_compiler.chunk.write(OpCode::LoadNull, 0);
-        func_compiler.chunk.write(OpCode::Return, 0);
+        func_compiler.chunk.write(OpCode::LoadNull, Span::default());
+        func_compiler.chunk.write(OpCode::Return, Span::default());
         
         // create compiled function value
-        let func_value = Value::Function {
+        let captures = std::mem::take(&mut func_compiler.upvalues);
+        let is_closure = !captures.is_empty();
+        let func_value = Value::Function(Rc::new(Function {
             name: name.to_string(),
             param_count: params.len(),
-            chunk: func_compiler.chunk,
-        };
+            chunk: Rc::new(func_compiler.chunk),
+            captures,
+            upvalues: Vec::new(),
+            class_name: self.current_class.clone(),
+        }));
         
         let const_idx = self.chunk.add_constant(func_value);
-        self.chunk.write(OpCode::LoadConst(const_idx), self.current_line);
-        
-        // store function in variable
-        if self.scope_depth > 0 {
-            let idx = self.local_count;
-            self.locals.insert(name.to_string(), idx);
-            self.chunk.write(OpCode::SetLocal(idx), self.current_line);
-            self.local_count += 1;
+        if is_closure {
+            self.chunk.write(OpCode::Closure(const_idx), self.current_span);
         } else {
-            let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-            self.chunk.write(OpCode::SetGlobal(name_idx), self.current_line);
+            self.chunk.write(OpCode::LoadConst(const_idx), self.current_span);
         }
         
         Ok(())
@@ -1,4 +1,4 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::parser::ast::{Expr, Stmt};
 
@@ -9,13 +9,13 @@ impl Compiler {
         then_branch: &[Stmt],
         else_branch: &Option<Vec<Stmt>>,
         elseif_branches: &[(Expr, Vec<Stmt>)]
-    ) -> Result<(), String> {
+    ) -> CompileResult {
         // compile condition
         self.compile_expr(condition)?;
         
         // jump to else if condition is false
         let then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-        self.chunk.write(OpCode::Pop, self.current_line); // Pop condition
+        self.chunk.write(OpCode::Pop, self.current_span); // Pop condition
         
         // compile then branch
         self.begin_scope();
@@ -29,14 +29,14 @@ impl Compiler {
         
         // patch then jump to point here
         self.patch_jump(then_jump);
-        self.chunk.write(OpCode::Pop, self.current_line); // Pop condition
+        self.chunk.write(OpCode::Pop, self.current_span); // Pop condition
         
         // handle elseif branches
         let mut elseif_jumps = Vec::new();
         for (elseif_cond, elseif_body) in elseif_branches {
             self.compile_expr(elseif_cond)?;
             let elseif_then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-            self.chunk.write(OpCode::Pop, self.current_line);
+            self.chunk.write(OpCode::Pop, self.current_span);
             
             self.begin_scope();
             for stmt in elseif_body {
@@ -46,7 +46,7 @@ impl Compiler {
             
             elseif_jumps.push(self.emit_jump(OpCode::Jump(0)));
             self.patch_jump(elseif_then_jump);
-            self.chunk.write(OpCode::Pop, self.current_line);
+            self.chunk.write(OpCode::Pop, self.current_span);
         }
         
         // compile else branch if it exists
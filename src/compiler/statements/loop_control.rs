@@ -0,0 +1,44 @@
+use crate::compiler::{Compiler, CompileResult, LoopContext};
+use crate::vm::OpCode;
+
+impl Compiler {
+    pub fn compile_break_stmt(&mut self) -> CompileResult {
+        let jump = self.emit_loop_exit("break")?;
+        if let Some(current) = self.loops.last_mut() {
+            current.breaks.push(jump);
+        }
+        Ok(())
+    }
+    
+    pub fn compile_continue_stmt(&mut self) -> CompileResult {
+        let jump = self.emit_loop_exit("continue")?;
+        if let Some(current) = self.loops.last_mut() {
+            current.continues.push(jump);
+        }
+        Ok(())
+    }
+    
+    // jump out of the loop body, to be patched by the loop. the end of the
+    // body's scope is skipped, so its locals are popped here.
+    fn emit_loop_exit(&mut self, keyword: &str) -> CompileResult<usize> {
+        let body_start = match self.loops.last() {
+            Some(current) => current.body_start,
+            None => return Err(self.error(&format!("'{}' outside of a loop", keyword))),
+        };
+        self.pop_locals(body_start);
+        Ok(self.emit_jump(OpCode::Jump(0)))
+    }
+    
+    // start tracking a loop whose body scope has just begun
+    pub fn begin_loop(&mut self) {
+        self.loops.push(LoopContext {
+            body_start: self.locals.len(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+    }
+    
+    pub fn end_loop(&mut self) -> LoopContext {
+        self.loops.pop().expect("end_loop without begin_loop")
+    }
+}
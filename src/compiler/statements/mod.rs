@@ -8,4 +8,5 @@ mod while_stmt;
 mod for_stmt;
 mod function_stmt;
 mod return_stmt;
+mod loop_control;
 mod class_stmt;
@@ -1,11 +1,11 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_print(&mut self, expr: &Expr) -> Result<(), String> {
+    pub fn compile_print(&mut self, expr: &Expr) -> CompileResult {
         self.compile_expr(expr)?;
-        self.chunk.write(OpCode::Print, self.current_line);
+        self.chunk.write(OpCode::Print, self.current_span);
         Ok(())
     }
 }
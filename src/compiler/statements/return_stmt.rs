@@ -1,15 +1,25 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_return_stmt(&mut self, expr: &Option<Expr>) -> Result<(), String> {
+    pub fn compile_return_stmt(&mut self, expr: &Option<Expr>) -> CompileResult {
+        // a constructor always gives back the instance it's setting up
+        if self.is_constructor {
+            if expr.is_some() {
+                return Err(self.error("Cannot return a value from a constructor"));
+            }
+            self.chunk.write(OpCode::GetLocal(0), self.current_span);
+            self.chunk.write(OpCode::Return, self.current_span);
+            return Ok(());
+        }
+        
         if let Some(e) = expr {
             self.compile_expr(e)?;
         } else {
-            self.chunk.write(OpCode::LoadNull, self.current_line);
+            self.chunk.write(OpCode::LoadNull, self.current_span);
         }
-        self.chunk.write(OpCode::Return, self.current_line);
+        self.chunk.write(OpCode::Return, self.current_span);
         Ok(())
     }
 }
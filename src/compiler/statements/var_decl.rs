@@ -1,21 +1,20 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
-use crate::interpreter::Value;
 use crate::parser::ast::Expr;
 
 impl Compiler {
-    pub fn compile_var_decl(&mut self, name: &str, value: &Expr) -> Result<(), String> {
+    pub fn compile_var_decl(&mut self, name: &str, value: &Expr) -> CompileResult {
         self.compile_expr(value)?;
         
-        // store in local if we're in a local, otherwise global
+        // in a local scope the value stays on the stack as the variable's
+        // slot. the name is declared after the initializer, so `let x = x;`
+        // reads any outer x.
         if self.scope_depth > 0 {
-            let idx = self.local_count;
-            self.locals.insert(name.to_string(), idx);
-            self.chunk.write(OpCode::SetLocal(idx), self.current_line);
-            self.local_count += 1;
+            self.declare_local(name)?;
         } else {
-            let name_idx = self.chunk.add_constant(Value::String(name.to_string()));
-            self.chunk.write(OpCode::SetGlobal(name_idx), self.current_line);
+            let slot = self.global_slot(name);
+            self.chunk.write(OpCode::SetGlobal(slot), self.current_span);
+            self.chunk.write(OpCode::Pop, self.current_span);
         }
         
         Ok(())
@@ -1,9 +1,9 @@
-use crate::compiler::Compiler;
+use crate::compiler::{Compiler, CompileResult};
 use crate::vm::OpCode;
 use crate::parser::ast::{Expr, Stmt};
 
 impl Compiler {
-    pub fn compile_while_stmt(&mut self, condition: &Expr, body: &[Stmt]) -> Result<(), String> {
+    pub fn compile_while_stmt(&mut self, condition: &Expr, body: &[Stmt]) -> CompileResult {
         let loop_start = self.chunk.code.len();
         
         // compile condition
@@ -11,21 +11,33 @@ impl Compiler {
         
         // exit loop if condition is false
         let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
-        self.chunk.write(OpCode::Pop, self.current_line);
+        self.chunk.write(OpCode::Pop, self.current_span);
         
         // compile body
         self.begin_scope();
+        self.begin_loop();
         for stmt in body {
             self.compile_stmt(stmt)?;
         }
+        let loop_ctx = self.end_loop();
         self.end_scope();
         
+        // continue goes straight back to the condition
+        for jump in loop_ctx.continues {
+            self.patch_jump_to(jump, loop_start);
+        }
+        
         // loop back to condition
-        self.chunk.write(OpCode::Loop(loop_start), self.current_line);
+        self.chunk.write(OpCode::Loop(loop_start), self.current_span);
         
         // patch exit jump
         self.patch_jump(exit_jump);
-        self.chunk.write(OpCode::Pop, self.current_line);
+        self.chunk.write(OpCode::Pop, self.current_span);
+        
+        // break skips the condition, so jumps past its pop
+        for jump in loop_ctx.breaks {
+            self.patch_jump(jump);
+        }
         
         Ok(())
     }
@@ -0,0 +1,74 @@
+/*
+ * the host api for embedding sage in a rust program.
+ * an engine owns one vm, so globals, functions and classes defined by one
+ * eval are visible to the next.
+ */
+use crate::lexer::{Lexer, Tokenizer};
+use crate::parser::Parser;
+use crate::analyzer::Analyzer;
+use crate::compiler::Compiler;
+use crate::interpreter::Value;
+use crate::vm::VM;
+use crate::error::{errormsg, SageError, Span};
+
+pub struct Engine {
+    vm: VM,
+    analyzer: Analyzer, // remembers the classes earlier evals declared
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine { vm: VM::new(), analyzer: Analyzer::new() }
+    }
+    
+    // compile and run a piece of source. if it ends with an expression
+    // statement, that expression's value is returned, otherwise null.
+    // a syntax error in any statement fails the whole source.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Vec<SageError>> {
+        let mut lexer = Lexer::new(source);
+        let mut tokenizer = Tokenizer::new(&mut lexer);
+        let mut parser = Parser::new(&mut tokenizer).map_err(|e| vec![e])?;
+        let ast = parser.parse()?;
+        self.analyzer.check(&ast)?;
+        
+        let mut compiler = Compiler::with_globals(self.vm.global_names.clone());
+        let chunk = compiler.compile_repl(&ast).map_err(|e| vec![e])?;
+        
+        self.vm.run(chunk).map_err(|e| vec![e])
+    }
+    
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.vm.set_global(name, value.into());
+    }
+    
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.vm.get_global(name)
+    }
+    
+    // call a global function defined by a script
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, SageError> {
+        let callee = self.get_global(name).ok_or_else(|| {
+            errormsg::runtime_error(&format!("Undefined function '{}'", name), Span::default())
+        })?;
+        self.vm.call(callee, args)
+    }
+    
+    // make a rust function callable from scripts. an arity of None lets the
+    // function check its own arguments.
+    pub fn register_fn<F>(&mut self, name: &str, arity: Option<usize>, function: F)
+    where
+        F: Fn(&mut VM, &[Value]) -> Result<Value, String> + 'static,
+    {
+        self.vm.define_native(name, arity, function);
+    }
+    
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
@@ -1,24 +1,98 @@
 use std::process;
 use colored::Colorize;
+use crate::error::{SageError, ErrorKind, Span, TraceFrame};
 
 fn error_title() -> colored::ColoredString {
     "[ERR]".bold().red()
 }
 
-pub fn parser_error(message: &str, line: usize) -> ! {
-    eprintln!("{} Parse error: {} at line {}", error_title(), message, line);
-    process::exit(1);
+pub fn parser_error(message: &str, span: Span) -> SageError {
+    SageError::new(ErrorKind::Parse, message, span)
 }
-pub fn lexer_error(message: &str) -> ! {
-    eprintln!("{} Lexer error: {}", error_title(), message);
-    process::exit(1);
+pub fn lexer_error(message: &str, span: Span) -> SageError {
+    SageError::new(ErrorKind::Lexer, message, span)
+}
+pub fn compile_error(message: &str, span: Span) -> SageError {
+    SageError::new(ErrorKind::Compile, message, span)
+}
+pub fn runtime_error(message: &str, span: Span) -> SageError {
+    SageError::new(ErrorKind::Runtime, message, span)
 }
 
-pub fn runtime_error(message: &str, line: usize) -> ! {
-    eprintln!("{} Runtime error: {} at line {}", error_title(), message, line);
-    process::exit(1);
+// print an error along with the source line it points at, underlined,
+// followed by the call stack for runtime errors inside functions:
+//
+// [ERR] Runtime error: Division by zero
+//  --> tests/error/divide_zero.sge:6:9
+//   |
+// 6 | let z = x / 0;
+//   |         ^^^^^
+pub fn report(err: &SageError, filename: &str, source: &str) {
+    eprintln!("{} {}: {}", error_title(), err.kind, err.message);
+    report_source(err, filename, source);
+    report_trace(err, filename);
+}
+
+fn report_trace(err: &SageError, filename: &str) {
+    // a single frame is just the location already shown above
+    if err.trace.len() < 2 {
+        return;
+    }
+    eprintln!("{} traceback (most recent call first):", "=".bright_blue().bold());
+    // runaway recursion makes thousands of frames, so only the ends are shown
+    const SHOWN: usize = 10;
+    let print_frame = |frame: &TraceFrame| {
+        eprintln!("    at {} ({}:{}:{})", frame.name.bold(), filename, frame.span.line, frame.span.column);
+    };
+    if err.trace.len() <= SHOWN * 2 {
+        err.trace.iter().for_each(print_frame);
+        return;
+    }
+    err.trace[..SHOWN].iter().for_each(print_frame);
+    eprintln!("    ... {} more", err.trace.len() - SHOWN * 2);
+    err.trace[err.trace.len() - SHOWN..].iter().for_each(print_frame);
 }
-pub fn error(message: &str) -> ! {
+
+fn report_source(err: &SageError, filename: &str, source: &str) {
+    let span = err.span;
+    if span.line == 0 {
+        return;
+    }
+    let gutter = " ".repeat(span.line.to_string().len());
+    eprintln!("{}{} {}:{}:{}", gutter, "-->".bright_blue().bold(), filename, span.line, span.column);
+
+    let Some(line_text) = source.lines().nth(span.line - 1) else {
+        return;
+    };
+    // keep tabs so the carets line up with the source as printed
+    let padding: String = line_text.chars()
+        .take(span.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // only underline up to the end of the first line of the span
+    let width = source.get(span.offset..span.offset + span.len)
+        .map(|text| text.chars().take_while(|&c| c != '\n').count())
+        .unwrap_or(0)
+        .max(1);
+
+    let bar = "|".bright_blue().bold();
+    eprintln!("{} {}", gutter, bar);
+    eprintln!("{} {} {}", span.line.to_string().bright_blue().bold(), bar, line_text);
+    eprintln!("{} {} {}{}", gutter, bar, padding, "^".repeat(width).red().bold());
+}
+// an error that isn't tied to a place in the source, like a bad file
+pub fn fatal(message: &str) -> ! {
     eprintln!("{} {}", error_title(), message);
     process::exit(1);
 }
+pub fn error(err: &SageError, filename: &str, source: &str) -> ! {
+    report(err, filename, source);
+    process::exit(1);
+}
+pub fn errors(errs: &[SageError], filename: &str, source: &str) -> ! {
+    for err in errs {
+        report(err, filename, source);
+    }
+    eprintln!("{} {} error(s) in {}", error_title(), errs.len(), filename);
+    process::exit(1);
+}
@@ -1 +1,4 @@
 pub mod errormsg;
+pub mod sage_error;
+
+pub use sage_error::{SageError, ErrorKind, Span, TraceFrame};
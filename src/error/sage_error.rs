@@ -0,0 +1,83 @@
+use std::fmt;
+
+// which stage of the pipeline produced an error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Lexer,
+    Parse,
+    Compile,
+    Runtime,
+}
+
+// a region of the source. line and column are 1-based (column counts chars),
+// offset and len are in bytes. a line of 0 means the location is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, offset: usize, len: usize) -> Self {
+        Span { line, column, offset, len }
+    }
+
+    // a span starting at this one and running to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        let end = (other.offset + other.len).max(self.offset + self.len);
+        Span { len: end - self.offset, ..self }
+    }
+}
+
+// one active call when a runtime error happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub name: String, // function name, `Class::method` or `main`
+    pub span: Span,   // where that call was executing
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SageError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Span,
+    pub trace: Vec<TraceFrame>, // innermost call first, empty outside the vm
+}
+
+impl SageError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>, span: Span) -> Self {
+        SageError {
+            kind,
+            message: message.into(),
+            span,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_trace(mut self, trace: Vec<TraceFrame>) -> Self {
+        self.trace = trace;
+        self
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Lexer => "Lexer error",
+            ErrorKind::Parse => "Parse error",
+            ErrorKind::Compile => "Compilation error",
+            ErrorKind::Runtime => "Runtime error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for SageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} at line {}:{}", self.kind, self.message, self.span.line, self.span.column)
+    }
+}
+
+impl std::error::Error for SageError {}
@@ -1,3 +1,5 @@
 pub mod value;
+pub mod convert;
 
-pub use value::Value;
+pub use value::{Value, Function, Class, Capture, Upvalue, Map, MapKey, NativeFunction};
+pub use convert::ConversionError;
@@ -1,40 +1,381 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 use std::cell::RefCell;
 use crate::parser::ast::AccessModifier;
+use crate::vm::Chunk;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     Bool(bool),
     String(String),
-    List(Vec<Value>),
-    Function {
-        #[allow(dead_code)]
-        name: String,
-        param_count: usize,
-        chunk: crate::vm::Chunk,
-    },
-    Class {
-        name: String,
-        #[allow(dead_code)]
-        superclass: Option<Box<Value>>,
-        field_access: HashMap<String, AccessModifier>,  // field name -> access level
-        method_access: HashMap<String, AccessModifier>, // method name -> access level
-        methods: HashMap<String, Value>,                // instance methods
-        static_methods: HashMap<String, Value>,         // static methods
-    },
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Function(Rc<Function>),
+    Class(Rc<Class>),
     Instance {
-        class_name: String,
+        class: Rc<Class>,
         fields: Rc<RefCell<HashMap<String, Value>>>,
-        field_access: HashMap<String, AccessModifier>,  // track field access levels
-        methods: HashMap<String, Value>,                // instance methods
-        method_access: HashMap<String, AccessModifier>, // track method access levels
-        static_methods: HashMap<String, Value>,         // static methods from class
     },
     BoundMethod {
         receiver: Box<Value>,
         method: Box<Value>,
     },
+    NativeFunction(NativeFunction),
     Null,
 }
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Null => false,
+            Value::List(l) => !l.borrow().is_empty(),
+            Value::Map(m) => !m.borrow().is_empty(),
+            Value::String(s) => !s.is_empty(),
+            _ => true,
+        }
+    }
+    
+    // format the value for printing. `hook` gets the first go at every value,
+    // including ones inside lists and maps, so the vm can call toString.
+    // strings are quoted when they're inside a list or map.
+    pub fn format_with<E>(&self, hook: &mut dyn FnMut(&Value) -> Result<Option<String>, E>) -> Result<String, E> {
+        self.format_inner(hook, false, &mut Vec::new())
+    }
+    
+    // `seen` holds the lists and maps being printed, so a list that contains
+    // itself prints as [...] instead of recursing forever
+    fn format_inner<E>(
+        &self,
+        hook: &mut dyn FnMut(&Value) -> Result<Option<String>, E>,
+        nested: bool,
+        seen: &mut Vec<*const ()>,
+    ) -> Result<String, E> {
+        if let Some(s) = hook(self)? {
+            return Ok(s);
+        }
+        let s = match self {
+            Value::Number(n) => format_number(*n),
+            Value::Bool(b) => b.to_string(),
+            Value::String(s) if nested => format!("{:?}", s),
+            Value::String(s) => s.clone(),
+            Value::Null => "null".to_string(),
+            Value::List(items) => {
+                let ptr = Rc::as_ptr(items) as *const ();
+                if seen.contains(&ptr) {
+                    return Ok("[...]".to_string());
+                }
+                seen.push(ptr);
+                // cloned so toString can't find the list already borrowed
+                let items = items.borrow().clone();
+                let parts = items.iter()
+                    .map(|item| item.format_inner(hook, true, seen))
+                    .collect::<Result<Vec<_>, E>>()?;
+                seen.pop();
+                format!("[{}]", parts.join(", "))
+            }
+            Value::Map(map) => {
+                let ptr = Rc::as_ptr(map) as *const ();
+                if seen.contains(&ptr) {
+                    return Ok("{...}".to_string());
+                }
+                seen.push(ptr);
+                let entries: Vec<(MapKey, Value)> = {
+                    let map = map.borrow();
+                    map.keys().cloned().zip(map.values().cloned()).collect()
+                };
+                let parts = entries.iter()
+                    .map(|(key, value)| Ok(format!("{}: {}", key, value.format_inner(hook, true, seen)?)))
+                    .collect::<Result<Vec<_>, E>>()?;
+                seen.pop();
+                format!("{{{}}}", parts.join(", "))
+            }
+            Value::Function(function) => format!("<fn {}>", function.name),
+            Value::BoundMethod { method, .. } => return method.format_inner(hook, nested, seen),
+            Value::NativeFunction(native) => format!("<native fn {}>", native.name),
+            Value::Class(class) => format!("<class {}>", class.name),
+            Value::Instance { class, .. } => format!("<{} instance>", class.name),
+        };
+        Ok(s)
+    }
+    
+    // the name scripts see from type()
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function(_) | Value::BoundMethod { .. } | Value::NativeFunction(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance { .. } => "instance",
+            Value::Null => "null",
+        }
+    }
+}
+
+// a compiled function. the chunk is shared, so calling a function or making
+// a closure from it never copies its code.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub param_count: usize,
+    pub chunk: Rc<Chunk>,
+    pub captures: Vec<Capture>,              // where to find each upvalue when the closure is made
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>, // filled in by OpCode::Closure
+    pub class_name: Option<String>,          // class whose body it's written in, for access checks
+}
+
+// a class, shared by the global it's stored in and all of its instances.
+// the tables hold only what the class itself declares; lookups go through
+// the class and then its ancestors.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub field_access: HashMap<String, AccessModifier>,  // field name -> access level
+    pub method_access: HashMap<String, AccessModifier>, // method name -> access level
+    pub methods: HashMap<String, Value>,                // instance methods
+    pub static_methods: HashMap<String, Value>,         // static methods
+    pub static_field_access: HashMap<String, AccessModifier>, // static field name -> access level
+    pub static_fields: RefCell<HashMap<String, Value>>, // values of the static fields declared here
+    ancestors: Vec<Rc<Class>>,                          // superclass chain, nearest first
+}
+
+impl Class {
+    // the hidden method that gives a class's declared fields their starting
+    // values. it can't clash with a real method, as it isn't a valid name.
+    pub const FIELD_INITIALIZER: &'static str = "(fields)";
+    // the same for static fields, a static method run once the class is defined
+    pub const STATIC_INITIALIZER: &'static str = "(static fields)";
+    
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        field_access: HashMap<String, AccessModifier>,
+        method_access: HashMap<String, AccessModifier>,
+        methods: HashMap<String, Value>,
+        static_methods: HashMap<String, Value>,
+        static_field_access: HashMap<String, AccessModifier>,
+    ) -> Class {
+        // the method resolution order is worked out once, here
+        let ancestors = match &superclass {
+            Some(superclass) => std::iter::once(superclass.clone())
+                .chain(superclass.ancestors.iter().cloned())
+                .collect(),
+            None => Vec::new(),
+        };
+        Class {
+            name,
+            superclass,
+            field_access,
+            method_access,
+            methods,
+            static_methods,
+            static_field_access,
+            static_fields: RefCell::new(HashMap::new()),
+            ancestors,
+        }
+    }
+    
+    // the class followed by its ancestors, in method resolution order
+    pub fn lineage(&self) -> impl Iterator<Item = &Class> {
+        std::iter::once(self).chain(self.ancestors.iter().map(|class| &**class))
+    }
+    
+    pub fn find_method(&self, name: &str) -> Option<&Value> {
+        self.lineage().find_map(|class| class.methods.get(name))
+    }
+    
+    pub fn find_static_method(&self, name: &str) -> Option<&Value> {
+        self.lineage().find_map(|class| class.static_methods.get(name))
+    }
+    
+    // a member's access level, along with the class that declares it
+    pub fn find_field_access(&self, name: &str) -> Option<(&Class, &AccessModifier)> {
+        self.lineage().find_map(|class| class.field_access.get(name).map(|access| (class, access)))
+    }
+    
+    pub fn find_method_access(&self, name: &str) -> Option<(&Class, &AccessModifier)> {
+        self.lineage().find_map(|class| class.method_access.get(name).map(|access| (class, access)))
+    }
+    
+    // static fields are stored on the class that declares them, so a
+    // subclass shares its superclass's
+    pub fn find_static_field_access(&self, name: &str) -> Option<(&Class, &AccessModifier)> {
+        self.lineage().find_map(|class| class.static_field_access.get(name).map(|access| (class, access)))
+    }
+    
+    // the field initializers of the class and its ancestors, in the order
+    // they run: the root class's first
+    pub fn field_initializers(&self) -> Vec<&Value> {
+        let mut initializers: Vec<&Value> = self.lineage()
+            .filter_map(|class| class.methods.get(Class::FIELD_INITIALIZER))
+            .collect();
+        initializers.reverse();
+        initializers
+    }
+    
+    // whether this is `other` or one of its subclasses
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        self.lineage().any(|class| std::ptr::eq(class, other))
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut crate::vm::VM, &[Value]) -> Result<Value, String>;
+
+// a function implemented in rust. it's given the vm so it can call back into
+// scripts. errors are returned as messages and reported by the vm at the
+// call site.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Option<usize>, // None takes any number of arguments
+    pub function: Rc<NativeFn>,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format_with::<std::convert::Infallible>(&mut |_| Ok(None)) {
+            Ok(s) => write!(f, "{}", s),
+            Err(never) => match never {},
+        }
+    }
+}
+
+// whole numbers print without a decimal point
+pub fn format_number(n: f64) -> String {
+    if n == 0.0 {
+        // no "-0"
+        "0".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+// the types that can be used as map keys. numbers are stored by their bits
+// so they can be hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    String(String),
+    Number(u64),
+    Bool(bool),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::String(s) => Some(MapKey::String(s.clone())),
+            // -0 and 0 are the same key
+            Value::Number(n) if *n == 0.0 => Some(MapKey::Number(0.0f64.to_bits())),
+            Value::Number(n) => Some(MapKey::Number(n.to_bits())),
+            Value::Bool(b) => Some(MapKey::Bool(*b)),
+            _ => None,
+        }
+    }
+    
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::String(s) => Value::String(s.clone()),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Bool(b) => Value::Bool(*b),
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapKey::String(s) => write!(f, "{:?}", s),
+            MapKey::Number(bits) => write!(f, "{}", format_number(f64::from_bits(*bits))),
+            MapKey::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+// a map that remembers the order its keys were first inserted in
+#[derive(Clone, Default)]
+pub struct Map {
+    keys: Vec<MapKey>,
+    entries: HashMap<MapKey, Value>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Map::default()
+    }
+    
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        self.entries.get(key)
+    }
+    
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        if self.entries.insert(key.clone(), value).is_none() {
+            self.keys.push(key);
+        }
+    }
+    
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.entries.contains_key(key)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    
+    pub fn keys(&self) -> impl Iterator<Item = &MapKey> {
+        self.keys.iter()
+    }
+    
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.keys.iter().map(|key| &self.entries[key])
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.keys.iter().map(|key| (key.to_value(), &self.entries[key])))
+            .finish()
+    }
+}
+
+// a variable captured by a closure: either a local slot of the enclosing
+// function, or one of the enclosing function's own upvalues
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub is_local: bool,
+    pub index: usize,
+}
+
+// a captured variable. while the variable's frame is alive it points at the
+// stack slot; once the variable goes out of scope it holds the value itself.
+#[derive(Clone)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+// closures can capture themselves, so don't print through upvalues
+impl fmt::Debug for Upvalue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Upvalue::Open(slot) => write!(f, "<open upvalue {}>", slot),
+            Upvalue::Closed(_) => write!(f, "<closed upvalue>"),
+        }
+    }
+}
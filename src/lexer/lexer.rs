@@ -1,7 +1,11 @@
+use crate::error::Span;
+
 pub struct Lexer {
     pub input: Vec<char>,
     pub pos: usize,
     pub line: usize,
+    pub column: usize,
+    pub offset: usize, // byte offset of `pos` into the source
 }
 
 impl Lexer {
@@ -10,6 +14,8 @@ impl Lexer {
             input: input.chars().collect(),
             pos: 0,
             line: 1,
+            column: 1,
+            offset: 0,
         }
     }
 
@@ -25,7 +31,11 @@ impl Lexer {
         if let Some(c) = self.current_char() {
             if c == '\n' {
                 self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
             }
+            self.offset += c.len_utf8();
         }
         self.pos += 1;
     }
@@ -39,4 +49,14 @@ impl Lexer {
             }
         }
     }
+
+    // an empty span at the current position
+    pub fn span_start(&self) -> Span {
+        Span::new(self.line, self.column, self.offset, 0)
+    }
+
+    // the span from `start` up to the current position
+    pub fn span_from(&self, start: Span) -> Span {
+        Span { len: self.offset - start.offset, ..start }
+    }
 }
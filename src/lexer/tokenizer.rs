@@ -1,7 +1,7 @@
 use crate::lexer::Lexer;
 use crate::lexer::tokens::{Token, CurrentToken, default_symbol_map, multi_char_ops, keywords};
 use std::collections::HashMap;
-use crate::error::errormsg;
+use crate::error::{errormsg, SageError};
 
 pub struct Tokenizer<'a> {
     lexer: &'a mut Lexer,
@@ -85,34 +85,20 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
-    pub fn next_token(&mut self) -> CurrentToken {
+    pub fn next_token(&mut self) -> Result<CurrentToken, SageError> {
         self.lexer.skip_whitespace();
-        let line = self.lexer.line;
 
-        if let Some('/') = self.lexer.current_char() {
-            if let Some('/') = self.lexer.peek_char() {
-                self.skip_comment();
-                return self.next_token();
-            }
+        if let (Some('/'), Some('/')) = (self.lexer.current_char(), self.lexer.peek_char()) {
+            self.skip_comment();
+            return self.next_token();
         }
 
+        let start = self.lexer.span_start();
         if let Some(tok) = self.multi_char_op() {
-            return CurrentToken { token: tok, line };
+            return Ok(CurrentToken { token: tok, span: self.lexer.span_from(start) });
         }
         let token = match self.lexer.current_char() {
             Some('"') => self.string_lit(),
-            Some('t') | Some('f') => {
-                let id = self.identifier();
-                if let Token::Identifier(ref s) = id {
-                    if s == "true" {
-                        return CurrentToken { token: Token::Bool(true), line };
-                    } else if s == "false" {
-                        return CurrentToken { token: Token::Bool(false), line };
-                    }
-                }
-                id
-            }
-            Some('[') => self.list_lit(),
             Some(c) if c.is_ascii_digit() => self.number(),
             Some(c) if c.is_alphabetic() || c == '_' => self.identifier(),
             Some(c) => {
@@ -120,12 +106,16 @@ impl<'a> Tokenizer<'a> {
                     self.lexer.advance();
                     tok.clone()
                 } else {
-                    errormsg::lexer_error(&format!("Unexpected character: '{}'", c));
+                    self.lexer.advance();
+                    return Err(errormsg::lexer_error(
+                        &format!("Unexpected character: '{}'", c),
+                        self.lexer.span_from(start)
+                    ));
                 }
             }
             None => Token::EOF,
         };
-        CurrentToken { token, line }
+        Ok(CurrentToken { token, span: self.lexer.span_from(start) })
     }
 
     fn string_lit(&mut self) -> Token {
@@ -148,29 +138,4 @@ impl<'a> Tokenizer<'a> {
         }
         Token::StringLit(s)
     }
-
-    fn list_lit(&mut self) -> Token {
-        self.lexer.advance();
-        let mut items = Vec::new();
-        loop {
-            self.lexer.skip_whitespace();
-            if let Some(c) = self.lexer.current_char() {
-                if c == ']' {
-                    self.lexer.advance();
-                    break;
-                }
-                let item = self.next_token();
-                if item.token != Token::RBracket && item.token != Token::EOF {
-                    items.push(item.token);
-                }
-                self.lexer.skip_whitespace();
-                if let Some(',') = self.lexer.current_char() {
-                    self.lexer.advance();
-                }
-            } else {
-                break;
-            }
-        }
-        Token::List(items)
-    }
 }
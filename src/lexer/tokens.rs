@@ -1,21 +1,22 @@
 use std::collections::HashMap;
+use crate::error::Span;
 
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct CurrentToken {
     pub token: Token,
-    pub line: usize,
+    pub span: Span,
 }
 
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Token {
     // literals
     Number(f64),
     Identifier(String),
     StringLit(String),
     Bool(bool), // literal value: true/false
-    List(Vec<Token>),
 
     // ops
     Plus,
@@ -23,6 +24,7 @@ pub enum Token {
     Star,
     Percent, // %, modulo operator
     Slash,
+    Bang,        // !
     EqEq,        // ==
     NotEq,       // !=
     Less,        // <
@@ -49,6 +51,8 @@ pub enum Token {
     Else,
     ElseIfKw,
     Return,
+    BreakKw,
+    ContinueKw,
     NumKw,    // num keyword
     BoolKw,   // bool keyword
     ListKw,   // list keyword
@@ -67,6 +71,7 @@ pub enum Token {
     PublicKw,  // public keyword
     ProtectedKw, // protected keyword
     NewKw,    // new keyword
+    InstanceOfKw, // instanceof keyword, also spelled `is`
 
     // other
     Comma, // ,
@@ -85,6 +90,7 @@ pub fn default_symbol_map() -> HashMap<char, Token> {
     map.insert('*', Token::Star);
     map.insert('%', Token::Percent);
     map.insert('/', Token::Slash);
+    map.insert('!', Token::Bang);
     map.insert('(', Token::LParen);
     map.insert(')', Token::RParen);
     map.insert(':', Token::Colon);
@@ -124,6 +130,8 @@ pub fn keywords() -> HashMap<&'static str, Token> {
     map.insert("else", Token::Else);
     map.insert("elseif", Token::ElseIfKw);
     map.insert("return", Token::Return);
+    map.insert("break", Token::BreakKw);
+    map.insert("continue", Token::ContinueKw);
     map.insert("num", Token::NumKw);
     map.insert("bool", Token::BoolKw);
     map.insert("list", Token::ListKw);
@@ -142,5 +150,7 @@ pub fn keywords() -> HashMap<&'static str, Token> {
     map.insert("public", Token::PublicKw);
     map.insert("protected", Token::ProtectedKw);
     map.insert("new", Token::NewKw);
+    map.insert("instanceof", Token::InstanceOfKw);
+    map.insert("is", Token::InstanceOfKw);
     map
 }
@@ -0,0 +1,16 @@
+/*
+ * sagelang as a library: everything the binary uses, plus an Engine for
+ * running sage code from a rust host.
+ */
+pub mod lexer;
+pub mod parser;
+pub mod analyzer;
+pub mod interpreter;
+pub mod compiler;
+pub mod vm;
+pub mod error;
+mod engine;
+
+pub use engine::Engine;
+pub use interpreter::{Value, ConversionError};
+pub use error::SageError;
@@ -1,60 +1,115 @@
-mod lexer;
-mod parser;
-mod interpreter;
-mod compiler;
-mod vm;
-mod error;
+mod repl;
 
 use std::fs;
 use std::env;
-use crate::error::errormsg;
+use std::path::Path;
+use sagelang::{lexer, parser, analyzer, compiler, vm};
+use sagelang::vm::bytecode::Bytecode;
+use sagelang::error::errormsg;
 use colored::Colorize;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: {} <filename> [--debug]", args[0]);
+        repl::run(false);
+        return;
+    }
+    if args[1] == "--debug" {
+        repl::run(true);
+        return;
+    }
+    if args[1] == "compile" {
+        compile_command(&args[2..]);
         return;
     }
     let filename = &args[1];
     let debug = args.len() > 2 && args[2] == "--debug";
 
+    // precompiled files skip straight to the vm
+    if filename.ends_with(".sgc") {
+        run_bytecode(filename, debug);
+        return;
+    }
+
     let contents = fs::read_to_string(filename)
         .expect("Something went wrong reading the file");
+    let mut vm = vm::VM::new();
+    vm.debug = debug;
+    let chunk = compile_source(filename, &contents, debug, &vm);
+    
+    if let Err(e) = vm.run(chunk) {
+        errormsg::error(&e, filename, &contents);
+    }
+}
 
-    let mut lexer = lexer::Lexer::new(&contents);
-    let mut tokenizer = lexer::Tokenizer::new(&mut lexer);
-    let mut parser = parser::Parser::new(&mut tokenizer);
+// sagelang compile foo.sge [-o foo.sgc]
+fn compile_command(args: &[String]) {
+    let usage = "usage: sagelang compile <file.sge> [-o <file.sgc>]";
+    let (input, output) = match args {
+        [input] => (input, Path::new(input).with_extension("sgc")),
+        [input, flag, output] if flag == "-o" => (input, Path::new(output).to_path_buf()),
+        _ => errormsg::fatal(usage),
+    };
+
+    let contents = fs::read_to_string(input)
+        .unwrap_or_else(|e| errormsg::fatal(&format!("Could not read {}: {}", input, e)));
+    // globals are given the slots a fresh vm would give them
+    let vm = vm::VM::new();
+    let chunk = compile_source(input, &contents, false, &vm);
+
+    let globals = vm.global_names.borrow().names().to_vec();
+    let bytecode = Bytecode { source_name: input.clone(), globals, chunk };
+    let bytes = bytecode.to_bytes()
+        .unwrap_or_else(|e| errormsg::fatal(&format!("Could not compile {}: {}", input, e)));
+    fs::write(&output, bytes)
+        .unwrap_or_else(|e| errormsg::fatal(&format!("Could not write {}: {}", output.display(), e)));
+}
 
-    let ast = parser.parse();
+fn run_bytecode(filename: &str, debug: bool) {
+    let bytes = fs::read(filename)
+        .unwrap_or_else(|e| errormsg::fatal(&format!("Could not read {}: {}", filename, e)));
+    let bytecode = Bytecode::from_bytes(&bytes)
+        .unwrap_or_else(|e| errormsg::fatal(&format!("{}: {}", filename, e)));
+
+    let mut vm = vm::VM::new();
+    vm.debug = debug;
+    vm.global_names.borrow_mut().link(&bytecode.globals)
+        .unwrap_or_else(|e| errormsg::fatal(&format!("{}: {}", filename, e)));
+
+    if let Err(e) = vm.run(bytecode.chunk) {
+        // show the source line if the source is still around
+        let source = fs::read_to_string(&bytecode.source_name).unwrap_or_default();
+        errormsg::error(&e, &bytecode.source_name, &source);
+    }
+}
+
+// lex, parse and compile a source file to run on `vm`, reporting errors and
+// exiting if there are any
+fn compile_source(filename: &str, contents: &str, debug: bool, vm: &vm::VM) -> vm::Chunk {
+    let mut lexer = lexer::Lexer::new(contents);
+    let mut tokenizer = lexer::Tokenizer::new(&mut lexer);
+    let parsed = match parser::Parser::new(&mut tokenizer) {
+        Ok(mut parser) => parser.parse(),
+        Err(e) => Err(vec![e]),
+    };
+    let ast = match parsed {
+        Ok(ast) => ast,
+        Err(errs) => errormsg::errors(&errs, filename, contents),
+    };
     
     if debug {
         let ast_str = format!("{:?}", ast);
         fs::write("ast.txt", ast_str).expect("[ERR] Failed to write AST");
-        println!("{} {}", "[DEBUG]".bright_blue(), "AST written to ast.txt");
+        println!("{} AST written to ast.txt", "[DEBUG]".bright_blue());
+    }
+    
+    if let Err(errs) = analyzer::Analyzer::new().check(&ast) {
+        errormsg::errors(&errs, filename, contents);
     }
 
-    let mut compiler = compiler::Compiler::new();
-    let chunk = match compiler.compile(&ast) {
+    let mut compiler = compiler::Compiler::with_globals(vm.global_names.clone());
+    match compiler.compile(&ast) {
         Ok(chunk) => chunk,
-        Err(e) => {
-            errormsg::error(&format!("Compilation error: {}", e));
-        }
-    };
-
-    let mut vm = vm::VM::new();
-    vm.debug = debug;
-    
-    if let Err(e) = vm.run(chunk) {
-        if let Some(line_str) = e.strip_suffix(']').and_then(|s| s.rsplit("[line ").next()) {
-            if let Ok(line) = line_str.parse::<usize>() {
-                let message = e.split(" [line ").next().unwrap_or(&e);
-                errormsg::runtime_error(message, line);
-            } else {
-                errormsg::runtime_error(&e, 0);
-            }
-        } else {
-            errormsg::runtime_error(&e, 0);
-        }
+        Err(e) => errormsg::error(&e, filename, contents),
     }
 }
@@ -1,4 +1,5 @@
 use crate::lexer::tokens::Token;
+use crate::error::Span;
 
 #[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
@@ -19,129 +20,161 @@ pub struct Param {
 pub enum Expr {
     Number {
         value: f64,
-        line: usize,
+        span: Span,
     },
 
     Identifier {
         name: String,
-        line: usize,
+        span: Span,
     },
 
     StringLit {
         value: String,
-        line: usize,
+        span: Span,
     },
 
     Bool {
         value: bool,
-        line: usize,
+        span: Span,
     },
 
     List {
         items: Vec<Expr>,
-        line: usize,
+        span: Span,
+    },
+
+    Map {
+        entries: Vec<(Expr, Expr)>,
+        span: Span,
     },
 
     UnaryOp {
         op: Token,
         right: Box<Expr>,
-        line: usize,
+        span: Span,
     },
 
     BinaryOp {
         left: Box<Expr>,
         op: Token,
         right: Box<Expr>,
-        line: usize,
+        span: Span,
     },
     Grouping {
         expr: Box<Expr>,
-        line: usize,
+        span: Span,
     },
     Call {
         callee: Box<Expr>,
         args: Vec<Expr>,
-        line: usize,
+        span: Span,
     },
     Get {
         object: Box<Expr>,
         name: String,
-        line: usize,
+        span: Span,
     },
     Set {
         object: Box<Expr>,
         name: String,
         value: Box<Expr>,
-        line: usize,
+        span: Span,
+    },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        span: Span,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        span: Span,
     },
     This {
-        line: usize,
+        span: Span,
     },
     Super {
         method: String,
-        line: usize,
+        span: Span,
+    },
+    New {
+        class: Box<Expr>,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    Lambda {
+        params: Vec<Param>,
+        body: Vec<Stmt>,
+        span: Span,
     },
 }
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::enum_variant_names)]
 pub enum Stmt {
     VarDecl {
         name: String,
         value: Expr,
-        line: usize,
+        span: Span,
     },
     Assign {
         name: String,
         value: Expr,
-        line: usize,
+        span: Span,
     },
     While {
         condition: Expr,
         body: Vec<Stmt>,
-        line: usize,
+        span: Span,
     },
     For {
         var: String,
         iterable: Expr,
         body: Vec<Stmt>,
-        line: usize,
+        span: Span,
     },
     Function {
         name: String,
         params: Vec<Param>,
         body: Vec<Stmt>,
-        line: usize,
+        span: Span,
     },
     If {
         condition: Expr,
         then_branch: Vec<Stmt>,
         else_branch: Option<Vec<Stmt>>,
         elseif_branches: Vec<(Expr, Vec<Stmt>)>,
-        line: usize,
+        span: Span,
     },
     Print {
         expr: Expr,
-        line: usize,
+        span: Span,
     },
     Return {
         value: Option<Expr>,
-        line: usize,
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
     },
     ExprStmt {
         expr: Expr,
-        line: usize,
+        span: Span,
     },
     Block {
         stmts: Vec<Stmt>,
-        line: usize,
+        span: Span,
     },
     Class {
         name: String,
         superclass: Option<String>,
         fields: Vec<Field>,
         methods: Vec<Method>,
-        line: usize,
+        span: Span,
     },
 }
 
@@ -156,39 +189,46 @@ pub struct Method {
 }
 
 impl Expr {
-    pub fn line(&self) -> usize {
+    pub fn span(&self) -> Span {
         match self {
-            Expr::Number { line, .. } => *line,
-            Expr::Identifier { line, .. } => *line,
-            Expr::StringLit { line, .. } => *line,
-            Expr::Bool { line, .. } => *line,
-            Expr::List { line, .. } => *line,
-            Expr::UnaryOp { line, .. } => *line,
-            Expr::BinaryOp { line, .. } => *line,
-            Expr::Grouping { line, .. } => *line,
-            Expr::Call { line, .. } => *line,
-            Expr::Get { line, .. } => *line,
-            Expr::Set { line, .. } => *line,
-            Expr::This { line } => *line,
-            Expr::Super { line, .. } => *line,
+            Expr::Number { span, .. } => *span,
+            Expr::Identifier { span, .. } => *span,
+            Expr::StringLit { span, .. } => *span,
+            Expr::Bool { span, .. } => *span,
+            Expr::List { span, .. } => *span,
+            Expr::Map { span, .. } => *span,
+            Expr::UnaryOp { span, .. } => *span,
+            Expr::BinaryOp { span, .. } => *span,
+            Expr::Grouping { span, .. } => *span,
+            Expr::Call { span, .. } => *span,
+            Expr::Get { span, .. } => *span,
+            Expr::Set { span, .. } => *span,
+            Expr::Index { span, .. } => *span,
+            Expr::SetIndex { span, .. } => *span,
+            Expr::This { span } => *span,
+            Expr::Super { span, .. } => *span,
+            Expr::New { span, .. } => *span,
+            Expr::Lambda { span, .. } => *span,
         }
     }
 }
 
 impl Stmt {
-    pub fn line(&self) -> usize {
+    pub fn span(&self) -> Span {
         match self {
-            Stmt::VarDecl { line, .. } => *line,
-            Stmt::Assign { line, .. } => *line,
-            Stmt::While { line, .. } => *line,
-            Stmt::For { line, .. } => *line,
-            Stmt::Function { line, .. } => *line,
-            Stmt::If { line, .. } => *line,
-            Stmt::Print { line, .. } => *line,
-            Stmt::Return { line, .. } => *line,
-            Stmt::ExprStmt { line, .. } => *line,
-            Stmt::Block { line, .. } => *line,
-            Stmt::Class { line, .. } => *line,
+            Stmt::VarDecl { span, .. } => *span,
+            Stmt::Assign { span, .. } => *span,
+            Stmt::While { span, .. } => *span,
+            Stmt::For { span, .. } => *span,
+            Stmt::Function { span, .. } => *span,
+            Stmt::If { span, .. } => *span,
+            Stmt::Print { span, .. } => *span,
+            Stmt::Return { span, .. } => *span,
+            Stmt::Break { span } => *span,
+            Stmt::Continue { span } => *span,
+            Stmt::ExprStmt { span, .. } => *span,
+            Stmt::Block { span, .. } => *span,
+            Stmt::Class { span, .. } => *span,
         }
     }
 }
@@ -197,4 +237,6 @@ impl Stmt {
 pub struct Field {
     pub name: String,
     pub access: AccessModifier,
+    pub is_static: bool,
+    pub value: Option<Expr>, // initializer, run when an instance is made (or the class, if static)
 }
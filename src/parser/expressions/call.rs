@@ -1,146 +1,183 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
-use crate::error::errormsg;
+use crate::error::{errormsg, Span};
 
 impl<'a> Parser<'a> {
-    pub fn call(&mut self) -> Expr {
-        let mut expr = match &self.current.token {
+    pub fn call(&mut self) -> ParseResult<Expr> {
+        let start = self.current.span;
+        let expr = match &self.current.token {
             Token::NewKw => {
-                self.advance();
+                self.advance()?;
                 // expect class name
-                if let Token::Identifier(class_name) = &self.current.token {
+                let class = if let Token::Identifier(class_name) = &self.current.token {
                     let name = class_name.clone();
-                    let id_line = self.current.line;
-                    self.advance();
-                    Expr::Identifier { name, line: id_line }
+                    let id_span = self.current.span;
+                    self.advance()?;
+                    Expr::Identifier { name, span: id_span }
                 } else {
-                    errormsg::parser_error("Expected class name after 'new'", self.current.line);
-                }
+                    return Err(errormsg::parser_error("Expected class name after 'new'", self.current.span));
+                };
+                // the argument list can be left off when there are no arguments
+                let args = if self.current.token == Token::LParen {
+                    self.arguments()?
+                } else {
+                    Vec::new()
+                };
+                Expr::New { class: Box::new(class), args, span: start.to(self.previous) }
             }
             Token::ThisKw => {
-                let this_line = self.current.line;
-                self.advance();
-                Expr::This { line: this_line }
+                self.advance()?;
+                Expr::This { span: start }
             }
             Token::SuperKw => {
-                let super_line = self.current.line;
-                self.advance();
-                self.eat(Token::Dot);
+                self.advance()?;
+                // super(...) is short for super.constructor(...)
+                if self.current.token == Token::LParen {
+                    return self.finish_call_chain(Expr::Super { method: "constructor".to_string(), span: start }, start);
+                }
+                self.eat(Token::Dot)?;
                 if let Token::Identifier(method) = &self.current.token {
                     let method_name = method.clone();
-                    self.advance();
-                    Expr::Super { method: method_name, line: super_line }
+                    self.advance()?;
+                    Expr::Super { method: method_name, span: start.to(self.previous) }
                 } else {
-                    errormsg::parser_error("Expected method name after 'super.'", self.current.line);
+                    return Err(errormsg::parser_error("Expected method name after 'super.'", self.current.span));
                 }
             }
             Token::Identifier(name) => {
                 let id = name.clone();
-                let id_line = self.current.line;
-                self.advance();
-                Expr::Identifier { name: id, line: id_line }
+                self.advance()?;
+                Expr::Identifier { name: id, span: start }
             }
-            Token::LParen => self.grouping(),
+            // the type names double as the built-in conversion functions
+            Token::StrKw | Token::NumKw => {
+                let id = if self.current.token == Token::StrKw { "str" } else { "num" };
+                self.advance()?;
+                Expr::Identifier { name: id.to_string(), span: start }
+            }
+            Token::LParen => self.grouping()?,
+            Token::Fn => self.lambda()?,
             Token::Number(n) => {
                 let num = *n;
-                let num_line = self.current.line;
-                self.advance();
-                Expr::Number { value: num, line: num_line }
+                self.advance()?;
+                Expr::Number { value: num, span: start }
             }
             Token::StringLit(s) => {
                 let val = s.clone();
-                let str_line = self.current.line;
-                self.advance();
-                Expr::StringLit { value: val, line: str_line }
+                self.advance()?;
+                Expr::StringLit { value: val, span: start }
             }
             Token::Bool(b) => {
                 let val = *b;
-                let bool_line = self.current.line;
-                self.advance();
-                Expr::Bool { value: val, line: bool_line }
-            }
-            Token::List(items) => {
-                let list_line = self.current.line;
-                // convert Vec<Token> to Vec<Expr>
-                let expr_items = items.iter().map(|tok| match tok {
-                    Token::Number(n) => Expr::Number { value: *n, line: list_line },
-                    Token::StringLit(s) => Expr::StringLit { value: s.clone(), line: list_line },
-                    Token::Bool(b) => Expr::Bool { value: *b, line: list_line },
-                    Token::Identifier(id) => Expr::Identifier { name: id.clone(), line: list_line },
-                    _ => {
-                        errormsg::parser_error(&format!("Unsupported list element: {:?}", tok), 0);
-                    }
-                }).collect();
-                self.advance();
-                Expr::List { items: expr_items, line: list_line }
+                self.advance()?;
+                Expr::Bool { value: val, span: start }
             }
             Token::LBracket => {
-                let bracket_line = self.current.line;
-                self.advance();
+                self.advance()?;
                 let mut items = Vec::new();
                 while self.current.token != Token::RBracket && self.current.token != Token::EOF {
-                    items.push(self.expr());
+                    items.push(self.expr()?);
+                    if self.current.token == Token::Comma {
+                        self.advance()?;
+                    }
+                }
+                self.eat(Token::RBracket)?;
+                Expr::List { items, span: start.to(self.previous) }
+            }
+            Token::OpenBrace => {
+                // map literal: { key: value, ... }
+                self.advance()?;
+                let mut entries = Vec::new();
+                while self.current.token != Token::CloseBrace && self.current.token != Token::EOF {
+                    let key = self.expr()?;
+                    self.eat(Token::Colon)?;
+                    let value = self.expr()?;
+                    entries.push((key, value));
                     if self.current.token == Token::Comma {
-                        self.advance();
+                        self.advance()?;
                     }
                 }
-                self.eat(Token::RBracket);
-                Expr::List { items, line: bracket_line }
+                self.eat(Token::CloseBrace)?;
+                Expr::Map { entries, span: start.to(self.previous) }
             }
-            Token::ElseIfKw | Token::If | Token::Let | Token::Fn | Token::Return | Token::WhileKw | Token::ForKw | Token::PrintKw | Token::Else => {
-                errormsg::parser_error(
+            Token::ElseIfKw | Token::If | Token::Let | Token::Return | Token::WhileKw | Token::ForKw | Token::PrintKw | Token::Else => {
+                return Err(errormsg::parser_error(
                     &format!("Unexpected statement keyword in expression: {:?}", self.current.token),
-                    self.current.line
-                );
+                    self.current.span
+                ));
             }
             _ => {
-                errormsg::parser_error(
+                return Err(errormsg::parser_error(
                     &format!("Unexpected token in call: {:?}", self.current.token),
-                    self.current.line
-                );
+                    self.current.span
+                ));
             }
         };
 
+        self.finish_call_chain(expr, start)
+    }
+    
+    // the calls, indexing and property accesses following a primary expression
+    fn finish_call_chain(&mut self, mut expr: Expr, start: Span) -> ParseResult<Expr> {
         loop {
             match &self.current.token {
                 Token::LParen => {
-                    let line = self.current.line;
-                    self.advance();
-                    let mut args = Vec::new();
-                    if self.current.token != Token::RParen {
-                        args.push(self.expr());
-                        while self.current.token == Token::Comma {
-                            self.advance();
-                            args.push(self.expr());
-                        }
-                    }
-                    self.eat(Token::RParen);
+                    let args = self.arguments()?;
                     expr = Expr::Call {
                         callee: Box::new(expr),
                         args,
-                        line,
+                        span: start.to(self.previous),
+                    };
+                }
+                Token::LBracket => {
+                    self.advance()?;
+                    let index = self.expr()?;
+                    self.eat(Token::RBracket)?;
+                    expr = Expr::Index {
+                        object: Box::new(expr),
+                        index: Box::new(index),
+                        span: start.to(self.previous),
                     };
                 }
                 Token::Dot => {
-                    let line = self.current.line;
-                    self.advance();
-                    if let Token::Identifier(name) = &self.current.token {
-                        let prop_name = name.clone();
-                        self.advance();
+                    self.advance()?;
+                    // `obj.class` is the object's class
+                    let name = match &self.current.token {
+                        Token::Identifier(name) => Some(name.clone()),
+                        Token::ClassKw => Some("class".to_string()),
+                        _ => None,
+                    };
+                    if let Some(prop_name) = name {
+                        self.advance()?;
                         expr = Expr::Get {
                             object: Box::new(expr),
                             name: prop_name,
-                            line,
+                            span: start.to(self.previous),
                         };
                     } else {
-                        errormsg::parser_error("Expected property name after '.'", self.current.line);
+                        return Err(errormsg::parser_error("Expected property name after '.'", self.current.span));
                     }
                 }
                 _ => break,
             }
         }
 
-        expr
+        Ok(expr)
+    }
+    
+    // a parenthesized, comma separated argument list
+    fn arguments(&mut self) -> ParseResult<Vec<Expr>> {
+        self.eat(Token::LParen)?;
+        let mut args = Vec::new();
+        if self.current.token != Token::RParen {
+            args.push(self.expr()?);
+            while self.current.token == Token::Comma {
+                self.advance()?;
+                args.push(self.expr()?);
+            }
+        }
+        self.eat(Token::RParen)?;
+        Ok(args)
     }
 }
@@ -1,24 +1,24 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn comparison(&mut self) -> Expr {
-        let mut node = self.term();
+    pub fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut node = self.term()?;
 
-        while matches!(self.current.token, Token::Less | Token::LessEq | Token::Greater | Token::GreaterEq) {
-            let line = self.current.line;
+        while matches!(self.current.token, Token::Less | Token::LessEq | Token::Greater | Token::GreaterEq | Token::InstanceOfKw) {
             let op = self.current.token.clone();
-            self.advance();
-            let right = self.term();
+            self.advance()?;
+            let right = self.term()?;
+            let span = node.span().to(right.span());
             node = Expr::BinaryOp {
                 left: Box::new(node),
                 op,
                 right: Box::new(right),
-                line,
+                span,
             };
         }
 
-        node
+        Ok(node)
     }
 }
@@ -1,24 +1,24 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn equality(&mut self) -> Expr {
-        let mut node = self.comparison();
+    pub fn equality(&mut self) -> ParseResult<Expr> {
+        let mut node = self.comparison()?;
 
-        while matches!(self.current.token, Token::EqEq | Token::NotEq | Token::And | Token::Or) {
-            let line = self.current.line;
+        while matches!(self.current.token, Token::EqEq | Token::NotEq) {
             let op = self.current.token.clone();
-            self.advance();
-            let right = self.comparison();
+            self.advance()?;
+            let right = self.comparison()?;
+            let span = node.span().to(right.span());
             node = Expr::BinaryOp {
                 left: Box::new(node),
                 op,
                 right: Box::new(right),
-                line,
+                span,
             };
         }
 
-        node
+        Ok(node)
     }
 }
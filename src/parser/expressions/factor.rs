@@ -1,24 +1,24 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn factor(&mut self) -> Expr {
-        let mut node = self.unary();
+    pub fn factor(&mut self) -> ParseResult<Expr> {
+        let mut node = self.unary()?;
 
         while matches!(self.current.token, Token::Star | Token::Slash | Token::Percent) {
-            let line = self.current.line;
             let op = self.current.token.clone();
-            self.advance();
-            let right = self.unary();
+            self.advance()?;
+            let right = self.unary()?;
+            let span = node.span().to(right.span());
             node = Expr::BinaryOp {
                 left: Box::new(node),
                 op,
                 right: Box::new(right),
-                line,
+                span,
             };
         }
 
-        node
+        Ok(node)
     }
 }
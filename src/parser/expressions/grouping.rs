@@ -1,13 +1,13 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn grouping(&mut self) -> Expr {
-        let line = self.current.line;
-        self.eat(Token::LParen);
-        let expr = self.expr();
-        self.eat(Token::RParen);
-        Expr::Grouping { expr: Box::new(expr), line }
+    pub fn grouping(&mut self) -> ParseResult<Expr> {
+        let start = self.current.span;
+        self.eat(Token::LParen)?;
+        let expr = self.expr()?;
+        self.eat(Token::RParen)?;
+        Ok(Expr::Grouping { expr: Box::new(expr), span: start.to(self.previous) })
     }
 }
@@ -0,0 +1,15 @@
+use crate::parser::{Parser, ParseResult};
+use crate::parser::ast::Expr;
+use crate::lexer::tokens::Token;
+
+// anonymous function expressions like:
+// function (a, b) { return a + b; }
+impl<'a> Parser<'a> {
+    pub fn lambda(&mut self) -> ParseResult<Expr> {
+        let start = self.current.span;
+        self.eat(Token::Fn)?;
+        let params = self.param_list()?;
+        let body = self.block_stmt()?;
+        Ok(Expr::Lambda { params, body, span: start.to(self.previous) })
+    }
+}
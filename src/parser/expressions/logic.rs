@@ -0,0 +1,44 @@
+use crate::parser::{Parser, ParseResult};
+use crate::parser::ast::Expr;
+use crate::lexer::tokens::Token;
+
+impl<'a> Parser<'a> {
+    // || binds loosest, then &&, then equality
+    pub fn logic_or(&mut self) -> ParseResult<Expr> {
+        let mut node = self.logic_and()?;
+
+        while matches!(self.current.token, Token::Or) {
+            let op = self.current.token.clone();
+            self.advance()?;
+            let right = self.logic_and()?;
+            let span = node.span().to(right.span());
+            node = Expr::BinaryOp {
+                left: Box::new(node),
+                op,
+                right: Box::new(right),
+                span,
+            };
+        }
+
+        Ok(node)
+    }
+
+    pub fn logic_and(&mut self) -> ParseResult<Expr> {
+        let mut node = self.equality()?;
+
+        while matches!(self.current.token, Token::And) {
+            let op = self.current.token.clone();
+            self.advance()?;
+            let right = self.equality()?;
+            let span = node.span().to(right.span());
+            node = Expr::BinaryOp {
+                left: Box::new(node),
+                op,
+                right: Box::new(right),
+                span,
+            };
+        }
+
+        Ok(node)
+    }
+}
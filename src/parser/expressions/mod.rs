@@ -1,3 +1,4 @@
+pub mod logic;
 pub mod equality;
 pub mod comparison;
 pub mod term;
@@ -5,12 +6,13 @@ pub mod factor;
 pub mod unary;
 pub mod grouping;
 pub mod call;
+pub mod lambda;
 
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 
 impl<'a> Parser<'a> {
-    pub fn expr(&mut self) -> Expr {
-        self.equality()
+    pub fn expr(&mut self) -> ParseResult<Expr> {
+        self.logic_or()
     }
 }
@@ -1,19 +1,20 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Expr;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn unary(&mut self) -> Expr {
-        if matches!(self.current.token, Token::Plus | Token::Minus) {
-            let line = self.current.line;
+    pub fn unary(&mut self) -> ParseResult<Expr> {
+        if matches!(self.current.token, Token::Plus | Token::Minus | Token::Bang) {
+            let start = self.current.span;
             let op = self.current.token.clone();
-            self.advance();
-            let right = self.unary();
-            return Expr::UnaryOp {
+            self.advance()?;
+            let right = self.unary()?;
+            let span = start.to(right.span());
+            return Ok(Expr::UnaryOp {
                 op,
                 right: Box::new(right),
-                line,
-            };
+                span,
+            });
         }
         self.call()
     }
@@ -5,39 +5,52 @@ pub mod ast;
 use crate::lexer::tokenizer::Tokenizer;
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token, CurrentToken};
-use crate::error::errormsg;
+use crate::error::{errormsg, SageError, Span};
+
+pub type ParseResult<T> = Result<T, SageError>;
 
 pub struct Parser<'a> {
     pub tokenizer: &'a mut Tokenizer<'a>,
     pub current: CurrentToken,
+    pub previous: Span, // span of the last token consumed
+    pub errors: Vec<SageError>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokenizer: &'a mut Tokenizer<'a>) -> Self {
-        let current = tokenizer.next_token();
-        Self { tokenizer, current }
+    pub fn new(tokenizer: &'a mut Tokenizer<'a>) -> ParseResult<Self> {
+        let current = tokenizer.next_token()?;
+        Ok(Self { tokenizer, current, previous: Span::default(), errors: Vec::new() })
     }
 
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    // parse the whole input, returning every syntax error found if there were any
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<SageError>> {
         let mut stmts = Vec::new();
         while self.current.token != Token::EOF {
-            stmts.push(self.statement());
+            if let Some(stmt) = self.statement() {
+                stmts.push(stmt);
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(std::mem::take(&mut self.errors))
         }
-        stmts
     }
 
-    pub fn advance(&mut self) {
-        self.current = self.tokenizer.next_token();
+    pub fn advance(&mut self) -> ParseResult<()> {
+        self.previous = self.current.span;
+        self.current = self.tokenizer.next_token()?;
+        Ok(())
     }
 
-    pub fn eat(&mut self, expected: Token) {
+    pub fn eat(&mut self, expected: Token) -> ParseResult<()> {
         if std::mem::discriminant(&self.current.token) == std::mem::discriminant(&expected) {
-            self.advance();
+            self.advance()
         } else {
-            errormsg::parser_error(
+            Err(errormsg::parser_error(
                 &format!("Expected {:?}, got {:?}", expected, self.current.token),
-                self.current.line
-            );
+                self.current.span
+            ))
         }
     }
 }
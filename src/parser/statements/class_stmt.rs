@@ -1,128 +1,155 @@
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::{Stmt, Method, Param, Field, AccessModifier};
 use crate::lexer::tokens::Token;
 use crate::error::errormsg;
 
 impl<'a> Parser<'a> {
-    pub fn class_declaration(&mut self) -> Stmt {
-        let line = self.current.line;
+    pub fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
         
         let name = if let Token::Identifier(n) = &self.current.token {
             let name = n.clone();
-            self.advance();
+            self.advance()?;
             name
         } else {
-            errormsg::parser_error("Expected class name", self.current.line);
+            return Err(errormsg::parser_error("Expected class name", self.current.span));
         };
         
         let superclass = if matches!(self.current.token, Token::Less) {
-            self.advance();
+            self.advance()?;
             if let Token::Identifier(super_name) = &self.current.token {
                 let super_name = super_name.clone();
-                self.advance();
+                self.advance()?;
                 Some(super_name)
             } else {
-                errormsg::parser_error("Expected superclass name", self.current.line);
+                return Err(errormsg::parser_error("Expected superclass name", self.current.span));
             }
         } else {
             None
         };
         
         if !matches!(self.current.token, Token::OpenBrace) {
-            errormsg::parser_error("Expected '{' after class name", self.current.line);
+            return Err(errormsg::parser_error("Expected '{' after class name", self.current.span));
         }
-        self.advance();
+        self.advance()?;
 
         let mut fields = Vec::new();
         let mut methods = Vec::new();
         
         while !matches!(self.current.token, Token::CloseBrace) && !matches!(self.current.token, Token::EOF) {
-            let access = if matches!(self.current.token, Token::PrivateKw) {
-                self.advance();
-                AccessModifier::Private
-            } else if matches!(self.current.token, Token::ProtectedKw) {
-                self.advance();
-                AccessModifier::Protected
-            } else if matches!(self.current.token, Token::PublicKw) {
-                self.advance();
-                AccessModifier::Public
-            } else {
-                AccessModifier::Public
-            };
-            
-            let is_static = if matches!(self.current.token, Token::StaticKw) {
-                self.advance();
-                true
-            } else {
-                false
-            };
+            // a mistake in one member is reported and skipped, so the rest
+            // of the class is still parsed as members
+            let member_start = self.current.span;
+            if let Err(e) = self.class_member(&mut fields, &mut methods) {
+                self.errors.push(e);
+                self.synchronize_member(member_start);
+            }
+        }
+        
+        if !matches!(self.current.token, Token::CloseBrace) {
+            return Err(errormsg::parser_error("Expected '}' after class body", self.current.span));
+        }
+        self.advance()?;
+        
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            fields,
+            methods,
+            span: start.to(self.previous),
+        })
+    }
+    
+    // parse one field or method declaration into `fields` or `methods`
+    fn class_member(&mut self, fields: &mut Vec<Field>, methods: &mut Vec<Method>) -> ParseResult<()> {
+        let access = if matches!(self.current.token, Token::PrivateKw) {
+            self.advance()?;
+            AccessModifier::Private
+        } else if matches!(self.current.token, Token::ProtectedKw) {
+            self.advance()?;
+            AccessModifier::Protected
+        } else if matches!(self.current.token, Token::PublicKw) {
+            self.advance()?;
+            AccessModifier::Public
+        } else {
+            AccessModifier::Public
+        };
+        
+        let is_static = if matches!(self.current.token, Token::StaticKw) {
+            self.advance()?;
+            true
+        } else {
+            false
+        };
+        
+        if matches!(self.current.token, Token::Fn) {
+            self.advance()?;
             
-            if matches!(self.current.token, Token::Fn) {
-                self.advance();
-
             let method_name = match &self.current.token {
                 Token::Identifier(n) => {
                     let name = n.clone();
-                    self.advance();
+                    self.advance()?;
                     name
                 }
-                Token::PrintKw | Token::Return | Token::StrKw | Token::NumKw | 
+                Token::PrintKw | Token::Return | Token::BreakKw | Token::ContinueKw | Token::StrKw | Token::NumKw | 
                 Token::BoolKw | Token::ListKw | Token::StaticKw | Token::If | 
                 Token::Else | Token::ElseIfKw | Token::WhileKw | Token::ForKw |
                 Token::Let | Token::Fn | Token::InKw | Token::ClassKw | 
                 Token::ThisKw | Token::SuperKw | Token::NewKw | Token::PrivateKw |
                 Token::PublicKw | Token::ProtectedKw => {
-                    errormsg::parser_error(
+                    return Err(errormsg::parser_error(
                         &format!("Cannot use keyword '{:?}' as method name", self.current.token),
-                        self.current.line
-                    );
+                        self.current.span
+                    ));
                 }
                 _ => {
-                    errormsg::parser_error("Expected method name", self.current.line);
+                    return Err(errormsg::parser_error("Expected method name", self.current.span));
                 }
             };
             
             if !matches!(self.current.token, Token::LParen) {
-                errormsg::parser_error("Expected '(' after method name", self.current.line);
+                return Err(errormsg::parser_error("Expected '(' after method name", self.current.span));
             }
-            self.advance();
+            self.advance()?;
             
             let mut params = Vec::new();
             while !matches!(self.current.token, Token::RParen) {
                 let param_name = if let Token::Identifier(n) = &self.current.token {
                     let name = n.clone();
-                    self.advance();
+                    self.advance()?;
                     name
                 } else {
-                    errormsg::parser_error("Expected parameter name", self.current.line);
+                    return Err(errormsg::parser_error("Expected parameter name", self.current.span));
                 };
-                
+            
                 params.push(Param { param_name });
-                
+            
                 if matches!(self.current.token, Token::Comma) {
-                    self.advance();
+                    self.advance()?;
                 }
             }
             
             if !matches!(self.current.token, Token::RParen) {
-                errormsg::parser_error("Expected ')' after parameters", self.current.line);
+                return Err(errormsg::parser_error("Expected ')' after parameters", self.current.span));
             }
-            self.advance();
+            self.advance()?;
             
             if !matches!(self.current.token, Token::OpenBrace) {
-                errormsg::parser_error("Expected '{' before method body", self.current.line);
+                return Err(errormsg::parser_error("Expected '{' before method body", self.current.span));
             }
-            self.advance();
+            self.advance()?;
             
             let mut body = Vec::new();
             while !matches!(self.current.token, Token::CloseBrace) && !matches!(self.current.token, Token::EOF) {
-                body.push(self.statement());
+                if let Some(stmt) = self.statement() {
+                    body.push(stmt);
+                }
             }
             
             if !matches!(self.current.token, Token::CloseBrace) {
-                errormsg::parser_error("Expected '}' after method body", self.current.line);
+                return Err(errormsg::parser_error("Expected '}' after method body", self.current.span));
             }
-            self.advance();
+            self.advance()?;
             
             methods.push(Method {
                 name: method_name,
@@ -130,36 +157,42 @@ impl<'a> Parser<'a> {
                 body,
                 is_static,
                 access,
-                });
-            } else if let Token::Identifier(field_name) = &self.current.token {
-                let field_name = field_name.clone();
-                self.advance();
-                
-                if !matches!(self.current.token, Token::Semicolon) {
-                    errormsg::parser_error("Expected ';' after field declaration", self.current.line);
-                }
-                self.advance();
-                
-                fields.push(Field {
-                    name: field_name,
-                    access,
-                });
+            });
+        } else if matches!(self.current.token, Token::Identifier(_) | Token::StrKw | Token::NumKw | Token::BoolKw | Token::ListKw) {
+            // a field can be given a type and a starting value, as in
+            // `private num count = 0;`. the type isn't checked.
+            if !matches!(self.current.token, Token::Identifier(_)) {
+                self.advance()?;
+            }
+            let field_name = if let Token::Identifier(n) = &self.current.token {
+                let name = n.clone();
+                self.advance()?;
+                name
+            } else {
+                return Err(errormsg::parser_error("Expected field name", self.current.span));
+            };
+            
+            let value = if matches!(self.current.token, Token::Assign) {
+                self.advance()?;
+                Some(self.expr()?)
             } else {
-                errormsg::parser_error("Expected 'function' or field name in class body", self.current.line);
+                None
+            };
+            
+            if !matches!(self.current.token, Token::Semicolon) {
+                return Err(errormsg::parser_error("Expected ';' after field declaration", self.current.span));
             }
+            self.advance()?;
+            
+            fields.push(Field {
+                name: field_name,
+                access,
+                is_static,
+                value,
+            });
+        } else {
+            return Err(errormsg::parser_error("Expected 'function' or field name in class body", self.current.span));
         }
-        
-        if !matches!(self.current.token, Token::CloseBrace) {
-            errormsg::parser_error("Expected '}' after class body", self.current.line);
-        }
-        self.advance();
-        
-        Stmt::Class {
-            name,
-            superclass,
-            fields,
-            methods,
-            line,
-        }
+        Ok(())
     }
 }
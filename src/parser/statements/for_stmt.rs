@@ -1,7 +1,7 @@
 /*
  * parser for for-in statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
 use crate::error::errormsg;
@@ -10,28 +10,28 @@ use crate::error::errormsg;
 // for (i in 1 .. 10) {  }, or
 // for (i in list) { }
 impl<'a> Parser<'a> {
-    pub fn for_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
+    pub fn for_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
         // eat for 
-        self.eat(Token::ForKw);
+        self.eat(Token::ForKw)?;
         // eat (
-        self.eat(Token::LParen);
+        self.eat(Token::LParen)?;
         // this is the variable
         let var = match &self.current.token {
             Token::Identifier(id) => id.clone(),
-            _ => errormsg::parser_error("Expected variable name in for loop", self.current.line),
+            _ => return Err(errormsg::parser_error("Expected variable name in for loop", self.current.span)),
         };
-        self.advance();
+        self.advance()?;
         // eat in
-        self.eat(Token::InKw);
+        self.eat(Token::InKw)?;
         // this is the iterable
-        let iterable = self.expr();
+        let iterable = self.expr()?;
         // eat )
-        self.eat(Token::RParen);
+        self.eat(Token::RParen)?;
         // this is the block {}
-        let body = self.block_stmt();
+        let body = self.block_stmt()?;
 
         // return for loop
-        Stmt::For { var, iterable, body, line }
+        Ok(Stmt::For { var, iterable, body, span: start.to(self.previous) })
     }
 }
@@ -1,41 +1,52 @@
 /*
  * parser for function statements.
  */
-use crate::parser::Parser;
-use crate::parser::ast::Stmt;
+use crate::parser::{Parser, ParseResult};
+use crate::parser::ast::{Stmt, Param};
 use crate::lexer::tokens::{Token};
 use crate::error::errormsg;
 
 impl<'a> Parser<'a> {
-    pub fn function_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
+    pub fn function_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
         // eat function
-        self.eat(Token::Fn);
+        self.eat(Token::Fn)?;
 
         // this is the function name
         let name = match &self.current.token {
             Token::Identifier(id) => id.clone(),
-            _ => errormsg::parser_error("Expected function name", self.current.line),
+            _ => return Err(errormsg::parser_error("Expected function name", self.current.span)),
         };
-        self.advance();
+        self.advance()?;
 
+        // this is the parameter list ()
+        let params = self.param_list()?;
+
+        // this is the block {}
+        let body = self.block_stmt()?;
+
+        // return function
+        Ok(Stmt::Function { name, params, body, span: start.to(self.previous) })
+    }
+
+    // a parenthesised, comma separated list of parameter names
+    pub fn param_list(&mut self) -> ParseResult<Vec<Param>> {
         // eat (
-        self.eat(Token::LParen);
+        self.eat(Token::LParen)?;
 
-        // this is the parameter list
         let mut params = Vec::new();
         if self.current.token != Token::RParen {
             loop {
                 match &self.current.token {
                     Token::Identifier(id) => {
-                        params.push(crate::parser::ast::Param { param_name: id.clone() });
-                        self.advance();
+                        params.push(Param { param_name: id.clone() });
+                        self.advance()?;
                     }
-                    _ => errormsg::parser_error("Expected identifier in function parameters", self.current.line),
+                    _ => return Err(errormsg::parser_error("Expected identifier in function parameters", self.current.span)),
                 }
 
                 if self.current.token == Token::Comma {
-                    self.advance();
+                    self.advance()?;
                     continue;
                 } else {
                     break;
@@ -43,12 +54,8 @@ impl<'a> Parser<'a> {
             }
         }
         // eat )
-        self.eat(Token::RParen);
-
-        // this is the block {}
-        let body = self.block_stmt();
+        self.eat(Token::RParen)?;
 
-        // return function
-        Stmt::Function { name, params, body, line }
+        Ok(params)
     }
 }
@@ -1,35 +1,35 @@
 /*
  * parser for if, else and elseif statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
 
 impl<'a> Parser<'a> {
-    pub fn if_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
-        self.eat(Token::If);
-        let condition = self.expr();
-        let then_branch = self.block_stmt();
+    pub fn if_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::If)?;
+        let condition = self.expr()?;
+        let then_branch = self.block_stmt()?;
         let mut elseif_branches = Vec::new();
         while self.current.token == Token::ElseIfKw {
-            self.advance();
-            let elseif_cond = self.expr();
-            let elseif_block = self.block_stmt();
+            self.advance()?;
+            let elseif_cond = self.expr()?;
+            let elseif_block = self.block_stmt()?;
             elseif_branches.push((elseif_cond, elseif_block));
         }
         let else_branch = if self.current.token == Token::Else {
-            self.advance();
-            Some(self.block_stmt())
+            self.advance()?;
+            Some(self.block_stmt()?)
         } else {
             None
         };
-        Stmt::If {
+        Ok(Stmt::If {
             condition,
             then_branch,
             else_branch,
             elseif_branches,
-            line,
-        }
+            span: start.to(self.previous),
+        })
     }
 }
@@ -1,28 +1,28 @@
 /*
  * parser for let statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
 use crate::error::errormsg;
 
 impl<'a> Parser<'a> {
-    pub fn let_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
-        self.eat(Token::Let);
+    pub fn let_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::Let)?;
 
         let name = match &self.current.token {
             Token::Identifier(id) => id.clone(),
-            _ => errormsg::parser_error("Expected identifier after let", self.current.line),
+            _ => return Err(errormsg::parser_error("Expected identifier after let", self.current.span)),
         };
-        self.advance();
+        self.advance()?;
 
-        self.eat(Token::Assign);
+        self.eat(Token::Assign)?;
 
-        let value = self.expr();
+        let value = self.expr()?;
 
-        self.eat(Token::Semicolon);
+        self.eat(Token::Semicolon)?;
 
-        Stmt::VarDecl { name, value, line }
+        Ok(Stmt::VarDecl { name, value, span: start.to(self.previous) })
     }
 }
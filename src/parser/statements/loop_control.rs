@@ -0,0 +1,24 @@
+/*
+ * parser for break and continue statements.
+ */
+use crate::parser::{Parser, ParseResult};
+use crate::parser::ast::Stmt;
+use crate::lexer::tokens::{Token};
+
+impl<'a> Parser<'a> {
+    pub fn break_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::BreakKw)?;
+        self.eat(Token::Semicolon)?;
+
+        Ok(Stmt::Break { span: start.to(self.previous) })
+    }
+
+    pub fn continue_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::ContinueKw)?;
+        self.eat(Token::Semicolon)?;
+
+        Ok(Stmt::Continue { span: start.to(self.previous) })
+    }
+}
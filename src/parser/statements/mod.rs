@@ -2,78 +2,185 @@ pub mod let_stmt;
 pub mod function_stmt;
 pub mod if_stmt;
 pub mod return_stmt;
+pub mod loop_control;
 pub mod block_stmt;
 pub mod while_stmt;
 pub mod for_stmt;
 pub mod print_stmt;
 pub mod class_stmt;
 
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
-use crate::error::errormsg;
+use crate::error::{errormsg, Span};
 
 impl<'a> Parser<'a> {
-    pub fn statement(&mut self) -> Stmt {
+    // parse a statement. on a syntax error the error is recorded, the parser
+    // skips ahead to the next statement boundary and None is returned, so
+    // parsing can carry on and report every error in the file.
+    pub fn statement(&mut self) -> Option<Stmt> {
+        let start = self.current.span;
+        match self.parse_statement() {
+            Ok(stmt) => Some(stmt),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+
+    // skip tokens until just after a ';' or a skipped block, or just before
+    // a '}' or a keyword that starts a statement
+    fn synchronize(&mut self, start: Span) {
+        // always make progress, even if the error was on the first token
+        if self.current.span == start && self.current.token != Token::EOF {
+            self.advance_recording();
+        }
+        let mut depth = 0;
+        loop {
+            match self.current.token {
+                Token::EOF => return,
+                Token::OpenBrace => {
+                    depth += 1;
+                    self.advance_recording();
+                }
+                Token::CloseBrace if depth > 0 => {
+                    depth -= 1;
+                    self.advance_recording();
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                Token::CloseBrace => return,
+                Token::Semicolon if depth == 0 => {
+                    self.advance_recording();
+                    return;
+                }
+                Token::Let | Token::Fn | Token::If | Token::Return | Token::WhileKw |
+                Token::ForKw | Token::PrintKw | Token::ClassKw | Token::BreakKw |
+                Token::ContinueKw if depth == 0 => return,
+                _ => self.advance_recording(),
+            }
+        }
+    }
+
+    // the same inside a class body: skip to just after a ';' or a skipped
+    // block, or to just before the next member keyword or the class's '}'
+    fn synchronize_member(&mut self, start: Span) {
+        if self.current.span == start && self.current.token != Token::EOF {
+            self.advance_recording();
+        }
+        let mut depth = 0;
+        loop {
+            match self.current.token {
+                Token::EOF => return,
+                Token::OpenBrace => {
+                    depth += 1;
+                    self.advance_recording();
+                }
+                Token::CloseBrace if depth > 0 => {
+                    depth -= 1;
+                    self.advance_recording();
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                Token::CloseBrace => return,
+                Token::Semicolon if depth == 0 => {
+                    self.advance_recording();
+                    return;
+                }
+                Token::Fn | Token::PrivateKw | Token::ProtectedKw | Token::PublicKw |
+                Token::StaticKw if depth == 0 => return,
+                _ => self.advance_recording(),
+            }
+        }
+    }
+    
+    // advance, keeping any lexer error rather than bailing out
+    fn advance_recording(&mut self) {
+        if let Err(e) = self.advance() {
+            self.errors.push(e);
+        }
+    }
+
+    fn parse_statement(&mut self) -> ParseResult<Stmt> {
         match &self.current.token {
             Token::Let => self.let_stmt(),
             Token::Fn => self.function_stmt(),
             Token::If => self.if_stmt(),
             Token::Return => self.return_stmt(),
+            Token::BreakKw => self.break_stmt(),
+            Token::ContinueKw => self.continue_stmt(),
             Token::OpenBrace => {
-                let line = self.current.line;
-                Stmt::Block { stmts: self.block_stmt(), line }
+                let start = self.current.span;
+                let stmts = self.block_stmt()?;
+                Ok(Stmt::Block { stmts, span: start.to(self.previous) })
             }
             Token::WhileKw => self.while_stmt(),
             Token::ForKw => self.for_stmt(),
             Token::PrintKw => self.print_stmt(),
             Token::ClassKw => {
-                self.advance();
+                self.advance()?;
                 self.class_declaration()
             }
             Token::Identifier(_) | Token::ThisKw => {
                 // Parse the left side as an expression
-                let expr = self.call();
+                let expr = self.expr()?;
                 
                 // Check if it's followed by assignment
                 if matches!(self.current.token, Token::Assign) {
-                    let line = self.current.line;
-                    self.advance(); // consume '='
-                    let value = self.expr();
-                    self.eat(Token::Semicolon);
+                    let target_span = expr.span();
+                    self.advance()?; // consume '='
+                    let value = self.expr()?;
+                    let span = target_span.to(value.span());
+                    self.eat(Token::Semicolon)?;
                     
                     // Check if it's property assignment or variable assignment
                     match expr {
                         crate::parser::ast::Expr::Get { object, name, .. } => {
                             // Convert Get to Set
-                            Stmt::ExprStmt {
+                            Ok(Stmt::ExprStmt {
                                 expr: crate::parser::ast::Expr::Set {
                                     object,
                                     name,
                                     value: Box::new(value),
-                                    line,
+                                    span,
+                                },
+                                span,
+                            })
+                        }
+                        crate::parser::ast::Expr::Index { object, index, .. } => {
+                            // Convert Index to SetIndex
+                            Ok(Stmt::ExprStmt {
+                                expr: crate::parser::ast::Expr::SetIndex {
+                                    object,
+                                    index,
+                                    value: Box::new(value),
+                                    span,
                                 },
-                                line,
-                            }
+                                span,
+                            })
                         }
                         crate::parser::ast::Expr::Identifier { name, .. } => {
                             // Simple variable assignment
-                            Stmt::Assign { name, value, line }
+                            Ok(Stmt::Assign { name, value, span })
                         }
-                        _ => errormsg::parser_error("Invalid assignment target", self.current.line),
+                        _ => Err(errormsg::parser_error("Invalid assignment target", target_span)),
                     }
                 } else {
-                    let line = expr.line();
+                    let span = expr.span();
                     // It's just an expression statement
-                    self.eat(Token::Semicolon);
-                    Stmt::ExprStmt { expr, line }
+                    self.eat(Token::Semicolon)?;
+                    Ok(Stmt::ExprStmt { expr, span })
                 }
             }
             _ => {
-                let expr = self.expr();
-                let line = expr.line();
-                self.eat(Token::Semicolon);
-                Stmt::ExprStmt { expr, line }
+                let expr = self.expr()?;
+                let span = expr.span();
+                self.eat(Token::Semicolon)?;
+                Ok(Stmt::ExprStmt { expr, span })
             }
         }
     }
@@ -1,16 +1,16 @@
 /*
  * parser for print statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::Token;
 
 impl<'a> Parser<'a> {
-    pub fn print_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
-        self.eat(Token::PrintKw);
-        let expr = self.expr();
-        self.eat(Token::Semicolon);
-        Stmt::Print { expr, line }
+    pub fn print_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::PrintKw)?;
+        let expr = self.expr()?;
+        self.eat(Token::Semicolon)?;
+        Ok(Stmt::Print { expr, span: start.to(self.previous) })
     }
 }
@@ -1,23 +1,23 @@
 /*
  * parser for return statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
 
 impl<'a> Parser<'a> {
-    pub fn return_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
-        self.eat(Token::Return);
+    pub fn return_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::Return)?;
 
         let value = if self.current.token != Token::Semicolon {
-            Some(self.expr())
+            Some(self.expr()?)
         } else {
             None
         };
 
-        self.eat(Token::Semicolon);
+        self.eat(Token::Semicolon)?;
 
-        Stmt::Return { value, line }
+        Ok(Stmt::Return { value, span: start.to(self.previous) })
     }
 }
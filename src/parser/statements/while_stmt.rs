@@ -1,18 +1,18 @@
 /*
  * parser for while statements.
  */
-use crate::parser::Parser;
+use crate::parser::{Parser, ParseResult};
 use crate::parser::ast::Stmt;
 use crate::lexer::tokens::{Token};
 
 impl<'a> Parser<'a> {
-    pub fn while_stmt(&mut self) -> Stmt {
-        let line = self.current.line;
-        self.eat(Token::WhileKw);
-        self.eat(Token::LParen);
-        let condition = self.expr();
-        self.eat(Token::RParen);
-        let body = self.block_stmt();
-        Stmt::While { condition, body, line }
+    pub fn while_stmt(&mut self) -> ParseResult<Stmt> {
+        let start = self.current.span;
+        self.eat(Token::WhileKw)?;
+        self.eat(Token::LParen)?;
+        let condition = self.expr()?;
+        self.eat(Token::RParen)?;
+        let body = self.block_stmt()?;
+        Ok(Stmt::While { condition, body, span: start.to(self.previous) })
     }
 }
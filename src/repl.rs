@@ -0,0 +1,118 @@
+/*
+ * interactive read-eval-print loop.
+ * a single vm is kept alive between inputs, so globals and classes persist.
+ */
+use std::io::{self, BufRead, Write};
+use colored::Colorize;
+use sagelang::lexer::{Lexer, Tokenizer};
+use sagelang::parser::Parser;
+use sagelang::analyzer::Analyzer;
+use sagelang::compiler::Compiler;
+use sagelang::interpreter::Value;
+use sagelang::vm::VM;
+use sagelang::error::{errormsg, SageError};
+
+pub fn run(debug: bool) {
+    let mut vm = VM::new();
+    vm.debug = debug;
+    let mut analyzer = Analyzer::new();
+
+    println!("{} (press Ctrl-D to exit)", "sagelang repl".bold());
+
+    let stdin = io::stdin();
+    let mut input = String::new();
+    loop {
+        // show a continuation prompt while a block is still open
+        let prompt = if input.is_empty() { ">> " } else { ".. " };
+        print!("{}", prompt.bright_blue());
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                break;
+            }
+            Ok(_) => input.push_str(&line),
+            Err(e) => {
+                eprintln!("Failed to read input: {}", e);
+                break;
+            }
+        }
+
+        let (depth, last) = scan(&input);
+        if depth > 0 {
+            continue;
+        }
+
+        let mut source = std::mem::take(&mut input);
+        match last {
+            None => continue,
+            // let a bare expression be typed without its semicolon
+            Some(c) if c != ';' && c != '}' => source.push_str("\n;"),
+            _ => {}
+        }
+
+        match eval(&mut vm, &mut analyzer, &source) {
+            Ok(Value::Null) => {}
+            Ok(value) => match vm.stringify(&value) {
+                Ok(s) => println!("{}", s),
+                Err(e) => errormsg::report(&e, "<repl>", &source),
+            },
+            Err(errs) => {
+                for e in &errs {
+                    errormsg::report(e, "<repl>", &source);
+                }
+            }
+        }
+    }
+}
+
+fn eval(vm: &mut VM, analyzer: &mut Analyzer, source: &str) -> Result<Value, Vec<SageError>> {
+    let mut lexer = Lexer::new(source);
+    let mut tokenizer = Tokenizer::new(&mut lexer);
+    let mut parser = Parser::new(&mut tokenizer).map_err(|e| vec![e])?;
+    let ast = parser.parse()?;
+    analyzer.check(&ast)?;
+
+    let mut compiler = Compiler::with_globals(vm.global_names.clone());
+    let chunk = compiler.compile_repl(&ast).map_err(|e| vec![e])?;
+
+    vm.run(chunk).map_err(|e| vec![e])
+}
+
+// returns the bracket nesting depth at the end of the input and the last
+// character that isn't whitespace or inside a comment
+fn scan(source: &str) -> (i32, Option<char>) {
+    let mut depth = 0;
+    let mut last = None;
+    let mut in_string = false;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                '\\' => { chars.next(); }
+                '"' => in_string = false,
+                _ => {}
+            }
+            last = Some(c);
+            continue;
+        }
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '"' => in_string = true,
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            last = Some(c);
+        }
+    }
+    (depth, last)
+}
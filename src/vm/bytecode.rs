@@ -0,0 +1,462 @@
+/*
+ * the .sgc file format: a compiled chunk written out so it can be run
+ * without lexing, parsing or compiling the source again.
+ *
+ * layout (all integers little-endian):
+ *   magic "SGC\0", format version (u16), source file name, global names
+ *   in slot order, main chunk
+ * a chunk is its name, its packed instructions as they are in memory, the
+ * offset and span of each instruction, then its constants. functions and
+ * classes in the constant pool carry their own nested chunks.
+ */
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::error::Span;
+use crate::interpreter::{Value, Function, Class, Capture};
+use crate::parser::ast::AccessModifier;
+use super::chunk::Chunk;
+use super::opcode::OpCode;
+
+pub const MAGIC: &[u8; 4] = b"SGC\0";
+
+// bump whenever the layout or the meaning of an instruction changes
+pub const FORMAT_VERSION: u16 = 8;
+
+// a compiled program, the source file it came from (used when reporting
+// errors) and the names of the global slots its code refers to
+pub struct Bytecode {
+    pub source_name: String,
+    pub globals: Vec<String>,
+    pub chunk: Chunk,
+}
+
+impl Bytecode {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut w = Writer { bytes: Vec::new() };
+        w.bytes.extend_from_slice(MAGIC);
+        w.bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        w.string(&self.source_name);
+        w.u32(self.globals.len())?;
+        for name in &self.globals {
+            w.string(name);
+        }
+        w.chunk(&self.chunk)?;
+        Ok(w.bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Bytecode, String> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("not a sagelang bytecode file".to_string());
+        }
+        let mut r = Reader { bytes, pos: MAGIC.len(), global_count: 0 };
+        let version = u16::from_le_bytes([r.u8()?, r.u8()?]);
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "bytecode format version {} is not supported (expected {}), recompile the source",
+                version, FORMAT_VERSION
+            ));
+        }
+        let source_name = r.string()?;
+        let global_count = r.u32()?;
+        let mut globals = Vec::new();
+        for _ in 0..global_count {
+            globals.push(r.string()?);
+        }
+        r.global_count = global_count;
+        let chunk = r.chunk(0, 0)?;
+        if r.pos != bytes.len() {
+            return Err("unexpected data after the end of the program".to_string());
+        }
+        Ok(Bytecode { source_name, globals, chunk })
+    }
+}
+
+// what a chunk's operands have to stay below
+struct Bounds {
+    globals: usize,
+    upvalues: usize,
+    slots: usize,
+}
+
+// check the operands the vm indexes with: constants, globals, locals,
+// upvalues and jump targets, which must land on an instruction or the end
+// of the chunk
+fn check_operands(chunk: &Chunk, instructions: &[(usize, OpCode)], bounds: &Bounds) -> Result<(), String> {
+    let invalid = |offset: usize, what: &str| format!("{} at offset {} in '{}'", what, offset, chunk.name);
+    let constant = |offset: usize, idx: usize| {
+        chunk.constants.get(idx).ok_or_else(|| invalid(offset, "invalid constant index"))
+    };
+    for (position, &(offset, instruction)) in instructions.iter().enumerate() {
+        match instruction {
+            OpCode::LoadConst(idx) => match constant(offset, idx)? {
+                // a function that captures has to go through Closure to get its upvalues
+                Value::Function(function) if !function.captures.is_empty() => {
+                    return Err(invalid(offset, "capturing function loaded without a closure"));
+                }
+                Value::Class(class) if !class_methods_bound(chunk, class, &instructions[position + 1..]) => {
+                    return Err(invalid(offset, "class with capturing methods loaded without closures for them"));
+                }
+                _ => {}
+            },
+            OpCode::GetGlobal(slot) | OpCode::SetGlobal(slot) if slot >= bounds.globals => {
+                return Err(invalid(offset, "invalid global slot"));
+            }
+            OpCode::GetLocal(idx) | OpCode::SetLocal(idx) | OpCode::CloseUpvalues(idx) if idx >= bounds.slots => {
+                return Err(invalid(offset, "invalid local slot"));
+            }
+            OpCode::GetProperty(idx) | OpCode::SetProperty(idx) | OpCode::Invoke(idx, _) | OpCode::GetSuper(idx)
+            | OpCode::Method(idx, _) => {
+                constant(offset, idx)?;
+            }
+            OpCode::GetUpvalue(idx) | OpCode::SetUpvalue(idx) if idx >= bounds.upvalues => {
+                return Err(invalid(offset, "invalid upvalue index"));
+            }
+            OpCode::Closure(idx) => {
+                let Value::Function(function) = constant(offset, idx)? else {
+                    return Err(invalid(offset, "closure of a constant that isn't a function"));
+                };
+                let in_bounds = |capture: &Capture| {
+                    capture.index < if capture.is_local { bounds.slots } else { bounds.upvalues }
+                };
+                if !function.captures.iter().all(in_bounds) {
+                    return Err(invalid(offset, "invalid capture index"));
+                }
+            }
+            OpCode::Jump(target) | OpCode::JumpIfFalse(target) | OpCode::JumpIfTrue(target) | OpCode::Loop(target) => {
+                let on_boundary = target == chunk.code.len()
+                    || instructions.binary_search_by_key(&target, |&(start, _)| start).is_ok();
+                if !on_boundary {
+                    return Err(invalid(offset, "invalid jump target"));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// methods that capture only get their upvalues from the Method instructions
+// the compiler puts right after loading the class (and linking it to its
+// superclass), so all of them have to be there. the class's ancestors
+// come from the file as they are, so none of theirs may capture.
+fn class_methods_bound(chunk: &Chunk, class: &Class, mut rest: &[(usize, OpCode)]) -> bool {
+    let captures = |method: &Value| matches!(method, Value::Function(function) if !function.captures.is_empty());
+    let ancestors_capture = class.lineage().skip(1)
+        .any(|ancestor| ancestor.methods.values().chain(ancestor.static_methods.values()).any(captures));
+    if ancestors_capture {
+        return false;
+    }
+    
+    if let [(_, OpCode::GetGlobal(_) | OpCode::GetLocal(_) | OpCode::GetUpvalue(_)), (_, OpCode::Inherit), tail @ ..] = rest {
+        rest = tail;
+    }
+    let mut bound = Vec::new();
+    while let [(_, OpCode::Closure(_)), (_, OpCode::Method(name_idx, is_static)), tail @ ..] = rest {
+        if let Some(Value::String(name)) = chunk.constants.get(*name_idx) {
+            bound.push((name.as_str(), *is_static != 0));
+        }
+        rest = tail;
+    }
+    let unbound = |methods: &HashMap<String, Value>, is_static: bool| {
+        methods.iter().any(|(name, method)| captures(method) && !bound.contains(&(name.as_str(), is_static)))
+    };
+    !unbound(&class.methods, false) && !unbound(&class.static_methods, true)
+}
+
+// value tags
+const NULL: u8 = 0;
+const NUMBER: u8 = 1;
+const BOOL: u8 = 2;
+const STRING: u8 = 3;
+const FUNCTION: u8 = 4;
+const CLASS: u8 = 5;
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn u32(&mut self, n: usize) -> Result<(), String> {
+        let n = u32::try_from(n).map_err(|_| format!("{} is too large for the bytecode format", n))?;
+        self.bytes.extend_from_slice(&n.to_le_bytes());
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) {
+        // strings longer than 4GB can't come out of a source file
+        self.bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        self.bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) -> Result<(), String> {
+        self.string(&chunk.name);
+        self.u32(chunk.code.len())?;
+        self.bytes.extend_from_slice(&chunk.code);
+        self.u32(chunk.spans.len())?;
+        for (offset, span) in &chunk.spans {
+            self.u32(*offset)?;
+            self.u32(span.line)?;
+            self.u32(span.column)?;
+            self.u32(span.offset)?;
+            self.u32(span.len)?;
+        }
+        self.u32(chunk.constants.len())?;
+        for constant in &chunk.constants {
+            self.value(constant)?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), String> {
+        match value {
+            Value::Null => self.u8(NULL),
+            Value::Number(n) => {
+                self.u8(NUMBER);
+                self.bytes.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Bool(b) => {
+                self.u8(BOOL);
+                self.u8(*b as u8);
+            }
+            Value::String(s) => {
+                self.u8(STRING);
+                self.string(s);
+            }
+            Value::Function(function) => {
+                self.u8(FUNCTION);
+                self.string(&function.name);
+                self.u32(function.param_count)?;
+                self.u32(function.captures.len())?;
+                for capture in &function.captures {
+                    self.u8(capture.is_local as u8);
+                    self.u32(capture.index)?;
+                }
+                self.optional_string(function.class_name.as_deref());
+                self.chunk(&function.chunk)?;
+            }
+            Value::Class(class) => self.class(class)?,
+            other => return Err(format!("cannot write {} to bytecode", other.type_name())),
+        }
+        Ok(())
+    }
+
+    fn class(&mut self, class: &Class) -> Result<(), String> {
+        self.u8(CLASS);
+        self.string(&class.name);
+        match &class.superclass {
+            Some(superclass) => {
+                self.u8(1);
+                self.class(superclass)?;
+            }
+            None => self.u8(0),
+        }
+        self.access_map(&class.field_access)?;
+        self.access_map(&class.method_access)?;
+        self.method_map(&class.methods)?;
+        self.method_map(&class.static_methods)?;
+        self.access_map(&class.static_field_access)?;
+        Ok(())
+    }
+    
+    fn optional_string(&mut self, s: Option<&str>) {
+        match s {
+            Some(s) => {
+                self.u8(1);
+                self.string(s);
+            }
+            None => self.u8(0),
+        }
+    }
+    
+    // maps are written sorted by key so the same program always produces
+    // the same file
+    fn access_map(&mut self, map: &HashMap<String, AccessModifier>) -> Result<(), String> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        self.u32(entries.len())?;
+        for (name, access) in entries {
+            self.string(name);
+            self.u8(match access {
+                AccessModifier::Public => 0,
+                AccessModifier::Private => 1,
+                AccessModifier::Protected => 2,
+            });
+        }
+        Ok(())
+    }
+
+    fn method_map(&mut self, map: &HashMap<String, Value>) -> Result<(), String> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        self.u32(entries.len())?;
+        for (name, method) in entries {
+            self.string(name);
+            self.value(method)?;
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    global_count: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| "bytecode file is truncated".to_string())?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "invalid string in bytecode file".to_string())
+    }
+
+    fn optional_string(&mut self) -> Result<Option<String>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.string()?)),
+        }
+    }
+    
+    // `upvalue_count` is how many upvalues the function owning the chunk
+    // will have when it runs, and `param_slots` how many stack slots its
+    // arguments (and `this`) take up
+    fn chunk(&mut self, upvalue_count: usize, param_slots: usize) -> Result<Chunk, String> {
+        let mut chunk = Chunk::new(self.string()?);
+        let code_len = self.u32()?;
+        chunk.code = self.take(code_len)?.to_vec();
+        // check every instruction decodes, so the vm never sees bad code
+        let mut instructions = Vec::new();
+        let mut ip = 0;
+        while ip < chunk.code.len() {
+            let start = ip;
+            let instruction = OpCode::decode(&chunk.code, &mut ip)
+                .ok_or_else(|| format!("invalid instruction at offset {} in '{}'", start, chunk.name))?;
+            instructions.push((start, instruction));
+        }
+        let span_count = self.u32()?;
+        for _ in 0..span_count {
+            let offset = self.u32()?;
+            let span = Span::new(self.u32()?, self.u32()?, self.u32()?, self.u32()?);
+            chunk.spans.push((offset, span));
+        }
+        let constant_count = self.u32()?;
+        for _ in 0..constant_count {
+            let constant = self.value()?;
+            chunk.constants.push(constant);
+        }
+        let bounds = Bounds {
+            globals: self.global_count,
+            upvalues: upvalue_count,
+            // every instruction leaves at most one more value on the stack,
+            // so no local sits further up than one slot per instruction
+            slots: param_slots + instructions.len(),
+        };
+        check_operands(&chunk, &instructions, &bounds)?;
+        Ok(chunk)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let tag = self.u8()?;
+        let value = match tag {
+            NULL => Value::Null,
+            NUMBER => {
+                let bytes = self.take(8)?;
+                let mut n = [0; 8];
+                n.copy_from_slice(bytes);
+                Value::Number(f64::from_le_bytes(n))
+            }
+            BOOL => Value::Bool(self.u8()? != 0),
+            STRING => Value::String(self.string()?),
+            FUNCTION => {
+                let name = self.string()?;
+                let param_count = self.u32()?;
+                let capture_count = self.u32()?;
+                let mut captures = Vec::new();
+                for _ in 0..capture_count {
+                    let is_local = self.u8()? != 0;
+                    let index = self.u32()?;
+                    captures.push(Capture { is_local, index });
+                }
+                let class_name = self.optional_string()?;
+                let chunk = self.chunk(capture_count, param_count + 1)?;
+                Value::Function(Rc::new(Function {
+                    name,
+                    param_count,
+                    chunk: Rc::new(chunk),
+                    captures,
+                    upvalues: Vec::new(),
+                    class_name,
+                }))
+            }
+            CLASS => {
+                let name = self.string()?;
+                let superclass = match self.u8()? {
+                    0 => None,
+                    _ => match self.value()? {
+                        Value::Class(superclass) => Some(superclass),
+                        _ => return Err("superclass in bytecode file is not a class".to_string()),
+                    },
+                };
+                Value::Class(Rc::new(Class::new(
+                    name,
+                    superclass,
+                    self.access_map()?,
+                    self.access_map()?,
+                    self.method_map()?,
+                    self.method_map()?,
+                    self.access_map()?,
+                )))
+            }
+            _ => return Err(format!("unknown constant type {} in bytecode file", tag)),
+        };
+        Ok(value)
+    }
+
+    fn access_map(&mut self) -> Result<HashMap<String, AccessModifier>, String> {
+        let count = self.u32()?;
+        let mut map = HashMap::new();
+        for _ in 0..count {
+            let name = self.string()?;
+            let access = match self.u8()? {
+                0 => AccessModifier::Public,
+                1 => AccessModifier::Private,
+                2 => AccessModifier::Protected,
+                other => return Err(format!("unknown access modifier {} in bytecode file", other)),
+            };
+            map.insert(name, access);
+        }
+        Ok(map)
+    }
+
+    fn method_map(&mut self) -> Result<HashMap<String, Value>, String> {
+        let count = self.u32()?;
+        let mut map = HashMap::new();
+        for _ in 0..count {
+            let name = self.string()?;
+            let method = self.value()?;
+            map.insert(name, method);
+        }
+        Ok(map)
+    }
+}
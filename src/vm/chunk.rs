@@ -1,12 +1,13 @@
 use crate::interpreter::Value;
-use super::opcode::OpCode;
+use crate::error::Span;
+use super::opcode::{self, OpCode};
 
 // a chunk of bytecode with its associated constant pool
 #[derive(Debug, Clone)]
 pub struct Chunk {
-    pub code: Vec<OpCode>,
+    pub code: Vec<u8>,              // packed instructions, see OpCode::encode
     pub constants: Vec<Value>,
-    pub lines: Vec<usize>,  // line numbers corresponding to each opcode
+    pub spans: Vec<(usize, Span)>,  // source span of the instruction starting at each offset
     pub name: String,
 }
 
@@ -15,15 +16,29 @@ impl Chunk {
         Chunk {
             code: Vec::new(),
             constants: Vec::new(),
-            lines: Vec::new(),
+            spans: Vec::new(),
             name,
         }
     }
     
     // add an instruction to the chunk
-    pub fn write(&mut self, op: OpCode, line: usize) {
-        self.code.push(op);
-        self.lines.push(line);
+    pub fn write(&mut self, op: OpCode, span: Span) {
+        self.spans.push((self.code.len(), span));
+        op.encode(&mut self.code);
+    }
+    
+    // point the jump instruction at `offset` to `target`
+    pub fn patch_jump(&mut self, offset: usize, target: usize) {
+        opcode::patch_jump(&mut self.code, offset, target);
+    }
+    
+    // the span of the instruction covering a byte offset
+    pub fn span_at(&self, offset: usize) -> Span {
+        let idx = self.spans.partition_point(|&(start, _)| start <= offset);
+        if idx == 0 {
+            return Span::default();
+        }
+        self.spans[idx - 1].1
     }
     
     // add a constant to the constant pool and return its index
@@ -37,161 +52,16 @@ impl Chunk {
         println!("== {} ==", self.name);
         let mut offset = 0;
         while offset < self.code.len() {
-            offset = self.disassemble_instruction(offset);
-        }
-    }
-    
-    fn disassemble_instruction(&self, offset: usize) -> usize {
-        print!("{:04} ", offset);
-        
-        match &self.code[offset] {
-            OpCode::LoadConst(idx) => {
-                println!("LoadConst {} ({:?})", idx, self.constants.get(*idx));
-                offset + 1
-            }
-            OpCode::LoadTrue => {
-                println!("LoadTrue");
-                offset + 1
-            }
-            OpCode::LoadFalse => {
-                println!("LoadFalse");
-                offset + 1
-            }
-            OpCode::LoadNull => {
-                println!("LoadNull");
-                offset + 1
-            }
-            OpCode::GetGlobal(idx) => {
-                println!("GetGlobal {}", idx);
-                offset + 1
-            }
-            OpCode::SetGlobal(idx) => {
-                println!("SetGlobal {}", idx);
-                offset + 1
-            }
-            OpCode::GetLocal(idx) => {
-                println!("GetLocal {}", idx);
-                offset + 1
-            }
-            OpCode::SetLocal(idx) => {
-                println!("SetLocal {}", idx);
-                offset + 1
-            }
-            OpCode::Add => {
-                println!("Add");
-                offset + 1
-            }
-            OpCode::Subtract => {
-                println!("Subtract");
-                offset + 1
-            }
-            OpCode::Multiply => {
-                println!("Multiply");
-                offset + 1
-            }
-            OpCode::Divide => {
-                println!("Divide");
-                offset + 1
-            }
-            OpCode::Modulo => {
-                println!("Modulo");
-                offset + 1
-            }
-            OpCode::Negate => {
-                println!("Negate");
-                offset + 1
-            }
-            OpCode::Equal => {
-                println!("Equal");
-                offset + 1
-            }
-            OpCode::NotEqual => {
-                println!("NotEqual");
-                offset + 1
-            }
-            OpCode::Greater => {
-                println!("Greater");
-                offset + 1
-            }
-            OpCode::GreaterEqual => {
-                println!("GreaterEqual");
-                offset + 1
-            }
-            OpCode::Less => {
-                println!("Less");
-                offset + 1
-            }
-            OpCode::LessEqual => {
-                println!("LessEqual");
-                offset + 1
-            }
-            OpCode::Jump(addr) => {
-                println!("Jump -> {}", addr);
-                offset + 1
-            }
-            OpCode::JumpIfFalse(addr) => {
-                println!("JumpIfFalse -> {}", addr);
-                offset + 1
-            }
-            OpCode::JumpIfTrue(addr) => {
-                println!("JumpIfTrue -> {}", addr);
-                offset + 1
-            }
-            OpCode::Loop(addr) => {
-                println!("Loop -> {}", addr);
-                offset + 1
-            }
-            OpCode::Call(arg_count) => {
-                println!("Call {}", arg_count);
-                offset + 1
-            }
-            OpCode::Return => {
-                println!("Return");
-                offset + 1
-            }
-            OpCode::MakeList(count) => {
-                println!("MakeList {}", count);
-                offset + 1
-            }
-            OpCode::BuildRange => {
-                println!("BuildRange");
-                offset + 1
-            }
-            OpCode::GetIndex => {
-                println!("GetIndex");
-                offset + 1
-            }
-            OpCode::DefineClass(name_idx) => {
-                println!("DefineClass {}", name_idx);
-                offset + 1
-            }
-            OpCode::GetProperty(name_idx) => {
-                println!("GetProperty {}", name_idx);
-                offset + 1
-            }
-            OpCode::SetProperty(name_idx) => {
-                println!("SetProperty {}", name_idx);
-                offset + 1
-            }
-            OpCode::GetSuper(method_idx) => {
-                println!("GetSuper {}", method_idx);
-                offset + 1
-            }
-            OpCode::Inherit => {
-                println!("Inherit");
-                offset + 1
-            }
-            OpCode::Pop => {
-                println!("Pop");
-                offset + 1
-            }
-            OpCode::Print => {
-                println!("Print");
-                offset + 1
-            }
-            OpCode::Dup => {
-                println!("Dup");
-                offset + 1
+            print!("{:04} ", offset);
+            match OpCode::decode(&self.code, &mut offset) {
+                Some(OpCode::LoadConst(idx)) => {
+                    println!("LoadConst {} ({:?})", idx, self.constants.get(idx));
+                }
+                Some(op) => println!("{:?}", op),
+                None => {
+                    println!("<invalid instruction>");
+                    return;
+                }
             }
         }
     }
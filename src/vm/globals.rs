@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+// the names of global variables, each given a fixed slot. the compiler and
+// the vm share one table, so the compiler can turn a global's name into the
+// index the vm stores it at.
+#[derive(Debug, Clone, Default)]
+pub struct GlobalTable {
+    names: Vec<String>,
+    slots: HashMap<String, usize>,
+}
+
+impl GlobalTable {
+    pub fn new() -> Self {
+        GlobalTable::default()
+    }
+
+    // the slot for a name, giving it the next free one if it doesn't have one
+    pub fn intern(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.slots.get(name) {
+            return slot;
+        }
+        let slot = self.names.len();
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), slot);
+        slot
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    pub fn name(&self, slot: usize) -> &str {
+        self.names.get(slot).map_or("<unknown>", String::as_str)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    // take on the slots code was compiled against. names this table already
+    // has must be in the same slots.
+    pub fn link(&mut self, names: &[String]) -> Result<(), String> {
+        for (slot, name) in names.iter().enumerate() {
+            match self.names.get(slot) {
+                Some(existing) if existing == name => {}
+                Some(existing) => {
+                    return Err(format!(
+                        "global slot {} is '{}' here but '{}' in the compiled code",
+                        slot, existing, name
+                    ));
+                }
+                None => {
+                    self.intern(name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
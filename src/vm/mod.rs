@@ -1,7 +1,12 @@
 pub mod opcode;
 pub mod chunk;
+pub mod prelude;
+pub mod bytecode;
+pub mod globals;
+#[allow(clippy::module_inception)]
 pub mod vm;
 
 pub use opcode::OpCode;
 pub use chunk::Chunk;
+pub use globals::GlobalTable;
 pub use vm::VM;
@@ -1,5 +1,8 @@
-// bytecode instructions
-#[derive(Debug, Clone, PartialEq)]
+// bytecode instructions. chunks store them packed into bytes: a tag byte
+// followed by the operands. operands are varints (one byte below 128)
+// except jump targets, which are always 4 bytes so a jump can be emitted
+// before its target is known and patched afterwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OpCode {
     // variables
     LoadConst(usize),       // load constant from constant pool
@@ -8,10 +11,13 @@ pub enum OpCode {
     LoadNull,               // load null value
     
     // variables
-    GetGlobal(usize),       // get global variable by name index
-    SetGlobal(usize),       // set global variable by name index
+    GetGlobal(usize),       // get global variable by slot
+    SetGlobal(usize),       // set global variable by slot
     GetLocal(usize),        // get local variable by stack index
     SetLocal(usize),        // set local variable by stack index
+    GetUpvalue(usize),      // get captured variable by upvalue index
+    SetUpvalue(usize),      // set captured variable by upvalue index
+    CloseUpvalues(usize),   // close captured locals at or above a stack index
     
     // arithmetic
     Add,
@@ -20,6 +26,7 @@ pub enum OpCode {
     Divide,
     Modulo,
     Negate,
+    Not,
     
     // comparison ops
     Equal,
@@ -37,22 +44,202 @@ pub enum OpCode {
     
     // functions
     Call(usize),            // call function
+    Closure(usize),         // make a closure from a function constant, capturing upvalues
     Return,                 // return from function
     
     // collections
     MakeList(usize),        // create list from N stack items
+    MakeMap(usize),         // create map from N key/value pairs on the stack
     BuildRange,             // build range from two numbers on stack
-    GetIndex,               // get item from list by index
+    GetIndex,               // get item from list, string or map
+    SetIndex,               // set list item or map entry, leaving the value
+    Len,                    // length of a list or string
+    GetIter,                // turn the value a for loop walks into something indexable
     
     // OOP-related
-    DefineClass(usize),     // define a class with name index
     GetProperty(usize),     // get property from object (name index)
     SetProperty(usize),     // set property on object (name index)
+    Invoke(usize, usize),   // call a method on an object (name index, arg count)
     GetSuper(usize),        // get method from superclass (name index)
-    Inherit,                // set up inheritance
+    Inherit,                // make a subclass from a class template and its superclass
+    Method(usize, usize),   // give the class below a closure as a method (name index, 1 if static)
+    New(usize),             // create an instance of a class and run its constructor (arg count)
+    InstanceOf,             // whether a value is an instance of a class or its subclasses
 
     // Other
     Pop,                    // pop and discard top of stack
     Print,                  // print top of stack
     Dup,                    // duplicate top of stack
 }
+
+impl OpCode {
+    // append the packed form of the instruction to `code`
+    pub fn encode(&self, code: &mut Vec<u8>) {
+        let (tag, operands): (u8, &[usize]) = match self {
+            OpCode::LoadConst(n) => (0, &[*n]),
+            OpCode::LoadTrue => (1, &[]),
+            OpCode::LoadFalse => (2, &[]),
+            OpCode::LoadNull => (3, &[]),
+            OpCode::GetGlobal(n) => (4, &[*n]),
+            OpCode::SetGlobal(n) => (5, &[*n]),
+            OpCode::GetLocal(n) => (6, &[*n]),
+            OpCode::SetLocal(n) => (7, &[*n]),
+            OpCode::GetUpvalue(n) => (8, &[*n]),
+            OpCode::SetUpvalue(n) => (9, &[*n]),
+            OpCode::CloseUpvalues(n) => (10, &[*n]),
+            OpCode::Add => (11, &[]),
+            OpCode::Subtract => (12, &[]),
+            OpCode::Multiply => (13, &[]),
+            OpCode::Divide => (14, &[]),
+            OpCode::Modulo => (15, &[]),
+            OpCode::Negate => (16, &[]),
+            OpCode::Not => (17, &[]),
+            OpCode::Equal => (18, &[]),
+            OpCode::NotEqual => (19, &[]),
+            OpCode::Greater => (20, &[]),
+            OpCode::GreaterEqual => (21, &[]),
+            OpCode::Less => (22, &[]),
+            OpCode::LessEqual => (23, &[]),
+            OpCode::Jump(addr) => (24, &[*addr]),
+            OpCode::JumpIfFalse(addr) => (25, &[*addr]),
+            OpCode::JumpIfTrue(addr) => (26, &[*addr]),
+            OpCode::Loop(addr) => (27, &[*addr]),
+            OpCode::Call(n) => (28, &[*n]),
+            OpCode::Closure(n) => (29, &[*n]),
+            OpCode::Return => (30, &[]),
+            OpCode::MakeList(n) => (31, &[*n]),
+            OpCode::MakeMap(n) => (32, &[*n]),
+            OpCode::BuildRange => (33, &[]),
+            OpCode::GetIndex => (34, &[]),
+            OpCode::SetIndex => (35, &[]),
+            OpCode::Len => (36, &[]),
+            OpCode::GetIter => (37, &[]),
+            OpCode::GetProperty(n) => (38, &[*n]),
+            OpCode::SetProperty(n) => (39, &[*n]),
+            OpCode::Invoke(name, argc) => (40, &[*name, *argc]),
+            OpCode::GetSuper(n) => (41, &[*n]),
+            OpCode::Inherit => (42, &[]),
+            OpCode::New(n) => (43, &[*n]),
+            OpCode::InstanceOf => (44, &[]),
+            OpCode::Pop => (45, &[]),
+            OpCode::Print => (46, &[]),
+            OpCode::Dup => (47, &[]),
+            OpCode::Method(name, is_static) => (48, &[*name, *is_static]),
+        };
+        code.push(tag);
+        for &operand in operands {
+            if self.is_jump() {
+                code.extend_from_slice(&(operand as u32).to_le_bytes());
+            } else {
+                write_varint(code, operand);
+            }
+        }
+    }
+    
+    fn is_jump(&self) -> bool {
+        matches!(self, OpCode::Jump(_) | OpCode::JumpIfFalse(_) | OpCode::JumpIfTrue(_) | OpCode::Loop(_))
+    }
+    
+    // read the instruction at `*ip` and move `ip` past it. None if the
+    // bytes aren't a valid instruction.
+    #[inline]
+    pub fn decode(code: &[u8], ip: &mut usize) -> Option<OpCode> {
+        let tag = *code.get(*ip)?;
+        *ip += 1;
+        let op = match tag {
+            0 => OpCode::LoadConst(read_varint(code, ip)?),
+            1 => OpCode::LoadTrue,
+            2 => OpCode::LoadFalse,
+            3 => OpCode::LoadNull,
+            4 => OpCode::GetGlobal(read_varint(code, ip)?),
+            5 => OpCode::SetGlobal(read_varint(code, ip)?),
+            6 => OpCode::GetLocal(read_varint(code, ip)?),
+            7 => OpCode::SetLocal(read_varint(code, ip)?),
+            8 => OpCode::GetUpvalue(read_varint(code, ip)?),
+            9 => OpCode::SetUpvalue(read_varint(code, ip)?),
+            10 => OpCode::CloseUpvalues(read_varint(code, ip)?),
+            11 => OpCode::Add,
+            12 => OpCode::Subtract,
+            13 => OpCode::Multiply,
+            14 => OpCode::Divide,
+            15 => OpCode::Modulo,
+            16 => OpCode::Negate,
+            17 => OpCode::Not,
+            18 => OpCode::Equal,
+            19 => OpCode::NotEqual,
+            20 => OpCode::Greater,
+            21 => OpCode::GreaterEqual,
+            22 => OpCode::Less,
+            23 => OpCode::LessEqual,
+            24 => OpCode::Jump(read_u32(code, ip)?),
+            25 => OpCode::JumpIfFalse(read_u32(code, ip)?),
+            26 => OpCode::JumpIfTrue(read_u32(code, ip)?),
+            27 => OpCode::Loop(read_u32(code, ip)?),
+            28 => OpCode::Call(read_varint(code, ip)?),
+            29 => OpCode::Closure(read_varint(code, ip)?),
+            30 => OpCode::Return,
+            31 => OpCode::MakeList(read_varint(code, ip)?),
+            32 => OpCode::MakeMap(read_varint(code, ip)?),
+            33 => OpCode::BuildRange,
+            34 => OpCode::GetIndex,
+            35 => OpCode::SetIndex,
+            36 => OpCode::Len,
+            37 => OpCode::GetIter,
+            38 => OpCode::GetProperty(read_varint(code, ip)?),
+            39 => OpCode::SetProperty(read_varint(code, ip)?),
+            40 => OpCode::Invoke(read_varint(code, ip)?, read_varint(code, ip)?),
+            41 => OpCode::GetSuper(read_varint(code, ip)?),
+            42 => OpCode::Inherit,
+            43 => OpCode::New(read_varint(code, ip)?),
+            44 => OpCode::InstanceOf,
+            45 => OpCode::Pop,
+            46 => OpCode::Print,
+            47 => OpCode::Dup,
+            48 => OpCode::Method(read_varint(code, ip)?, read_varint(code, ip)?),
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+// overwrite the target of the jump instruction at `offset`
+pub fn patch_jump(code: &mut [u8], offset: usize, target: usize) {
+    code[offset + 1..offset + 5].copy_from_slice(&(target as u32).to_le_bytes());
+}
+
+// unsigned LEB128: 7 bits per byte, high bit set on all but the last
+fn write_varint(code: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        code.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    code.push(n as u8);
+}
+
+#[inline]
+fn read_varint(code: &[u8], ip: &mut usize) -> Option<usize> {
+    // almost every operand fits in one byte
+    let byte = *code.get(*ip)?;
+    *ip += 1;
+    if byte & 0x80 == 0 {
+        return Some(byte as usize);
+    }
+    *ip -= 1;
+    let mut n = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *code.get(*ip)?;
+        *ip += 1;
+        n |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+        shift += 7;
+    }
+}
+
+fn read_u32(code: &[u8], ip: &mut usize) -> Option<usize> {
+    let bytes = code.get(*ip..*ip + 4)?;
+    *ip += 4;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
@@ -0,0 +1,79 @@
+use crate::interpreter::Value;
+use super::VM;
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the built-in functions every script can call
+pub fn install(vm: &mut VM) {
+    vm.define_native("len", Some(1), len);
+    vm.define_native("type", Some(1), |_, args| Ok(Value::String(args[0].type_name().to_string())));
+    vm.define_native("str", Some(1), str);
+    vm.define_native("num", Some(1), num);
+    vm.define_native("clock", Some(0), clock);
+    vm.define_native("input", None, input);
+    vm.define_native("assert", None, assert);
+}
+
+fn len(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let len = match &args[0] {
+        Value::String(s) => s.chars().count(),
+        Value::List(items) => items.borrow().len(),
+        Value::Map(map) => map.borrow().len(),
+        other => return Err(format!("len() expects a string, list or map, got {}", other.type_name())),
+    };
+    Ok(Value::Number(len as f64))
+}
+
+fn str(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    let s = vm.stringify(&args[0]).map_err(|e| e.message)?;
+    Ok(Value::String(s))
+}
+
+fn num(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Bool(b) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
+        Value::String(s) => s.trim().parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("Cannot convert {:?} to a number", s)),
+        other => Err(format!("Cannot convert {} to a number", other.type_name())),
+    }
+}
+
+// seconds since the unix epoch, for timing scripts
+fn clock(_vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?;
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+// read a line from stdin, optionally printing a prompt first.
+// returns null at end of input.
+fn input(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if args.len() > 1 {
+        return Err(format!("Expected 0 or 1 arguments but got {}", args.len()));
+    }
+    if let Some(prompt) = args.first() {
+        print!("{}", vm.stringify(prompt).map_err(|e| e.message)?);
+        io::stdout().flush().map_err(|e| e.to_string())?;
+    }
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+    if read == 0 {
+        return Ok(Value::Null);
+    }
+    let trimmed = line.trim_end_matches(['\n', '\r']);
+    Ok(Value::String(trimmed.to_string()))
+}
+
+fn assert(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(format!("Expected 1 or 2 arguments but got {}", args.len()));
+    }
+    if args[0].is_truthy() {
+        return Ok(Value::Null);
+    }
+    match args.get(1) {
+        Some(message) => Err(format!("Assertion failed: {}", vm.stringify(message).map_err(|e| e.message)?)),
+        None => Err("Assertion failed".to_string()),
+    }
+}
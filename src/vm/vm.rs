@@ -1,705 +1,1219 @@
-use crate::interpreter::Value;
+use crate::interpreter::{Value, Function, Class, Upvalue, Map, MapKey, NativeFunction};
+use crate::parser::ast::AccessModifier;
+use crate::error::{errormsg, SageError, Span, TraceFrame};
 use super::chunk::Chunk;
 use super::opcode::OpCode;
+use super::globals::GlobalTable;
+use super::prelude;
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
 
+// a call in progress. the frame shares the function it's running, so pushing
+// one costs the same however big the function is.
 #[derive(Debug, Clone)]
 pub struct CallFrame {
-    pub chunk: Chunk,
+    pub function: Rc<Function>,
     pub ip: usize,
     pub stack_offset: usize,
-    pub class_context: Option<String>,
+    pub discard_result: bool, // set for field initializers, which run ahead of the constructor
 }
 
+// how deep calls can nest before the vm gives up with a stack overflow
+pub const MAX_FRAMES: usize = 4096;
+// the same for calls from rust back into the vm, like toString while
+// printing. each of these nests on the native stack, so the limit is lower.
+pub const MAX_NESTED_CALLS: usize = 64;
+
 pub struct VM {
     pub stack: Vec<Value>,
     pub frames: Vec<CallFrame>,
-    pub globals: HashMap<String, Value>,
+    pub globals: Vec<Option<Value>>,                 // indexed by global slot, None until defined
+    pub global_names: Rc<RefCell<GlobalTable>>,      // shared with the compilers that target this vm
+    pub open_upvalues: Vec<Rc<RefCell<Upvalue>>>, // upvalues still pointing into the stack
     pub debug: bool,
+    nested_calls: usize,                             // calls into the vm from rust in progress
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        VM::new()
+    }
 }
 
 impl VM {
     pub fn new() -> Self {
-        VM {
+        let mut vm = VM {
             stack: Vec::new(),
             frames: Vec::new(),
-            globals: HashMap::new(),
+            globals: Vec::new(),
+            global_names: Rc::new(RefCell::new(GlobalTable::new())),
+            open_upvalues: Vec::new(),
             debug: false,
-        }
+            nested_calls: 0,
+        };
+        prelude::install(&mut vm);
+        vm
+    }
+    
+    // make a rust function callable from scripts as a global. an arity of
+    // None lets the function check its own arguments.
+    pub fn define_native<F>(&mut self, name: &str, arity: Option<usize>, function: F)
+    where
+        F: Fn(&mut VM, &[Value]) -> Result<Value, String> + 'static,
+    {
+        let native = NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Rc::new(function),
+        };
+        self.set_global(name, Value::NativeFunction(native));
+    }
+    
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let slot = self.global_names.borrow_mut().intern(name);
+        self.set_global_slot(slot, value);
     }
     
-    fn get_current_line(&self) -> usize {
-        if let Some(frame) = self.frames.last() {
-            let ip = if frame.ip > 0 { frame.ip - 1 } else { 0 };
-            frame.chunk.lines.get(ip).copied().unwrap_or(0)
-        } else {
-            0
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let slot = self.global_names.borrow().slot(name)?;
+        self.globals.get(slot).cloned().flatten()
+    }
+    
+    fn set_global_slot(&mut self, slot: usize, value: Value) {
+        if slot >= self.globals.len() {
+            self.globals.resize(slot + 1, None);
         }
+        self.globals[slot] = Some(value);
+    }
+    
+    // the span of the instruction a frame is currently executing
+    // (for callers, that's the call they're waiting on)
+    fn frame_span(frame: &CallFrame) -> Span {
+        let ip = if frame.ip > 0 { frame.ip - 1 } else { 0 };
+        frame.function.chunk.span_at(ip)
+    }
+    
+    fn get_current_span(&self) -> Span {
+        self.frames.last().map(Self::frame_span).unwrap_or_default()
+    }
+    
+    fn stack_trace(&self) -> Vec<TraceFrame> {
+        // a constructor waiting on its field initializers hasn't started yet
+        self.frames.iter().rev().filter(|frame| frame.ip > 0).map(|frame| TraceFrame {
+            name: frame.function.chunk.name.clone(),
+            span: Self::frame_span(frame),
+        }).collect()
+    }
+    
+    fn runtime_error(&self, message: &str) -> SageError {
+        errormsg::runtime_error(message, self.get_current_span()).with_trace(self.stack_trace())
     }
     
-    fn runtime_error(&self, message: &str) -> String {
-        let line = self.get_current_line();
-        format!("{} [line {}]", message, line)
+    // instructions that never call out of the current frame and only fail
+    // on their own, after writing the ip back themselves. pops can still
+    // underflow, but only on bytecode the compiler wouldn't emit.
+    fn is_plain(instruction: &OpCode) -> bool {
+        matches!(instruction,
+            OpCode::LoadConst(_) | OpCode::LoadTrue | OpCode::LoadFalse | OpCode::LoadNull
+            | OpCode::GetGlobal(_) | OpCode::SetGlobal(_) | OpCode::GetLocal(_) | OpCode::SetLocal(_)
+            | OpCode::GetUpvalue(_) | OpCode::SetUpvalue(_) | OpCode::CloseUpvalues(_)
+            | OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide | OpCode::Modulo
+            | OpCode::Negate | OpCode::Not | OpCode::Equal | OpCode::NotEqual
+            | OpCode::Greater | OpCode::GreaterEqual | OpCode::Less | OpCode::LessEqual
+            | OpCode::Jump(_) | OpCode::JumpIfFalse(_) | OpCode::JumpIfTrue(_) | OpCode::Loop(_)
+            | OpCode::Pop | OpCode::Dup)
     }
     
-    pub fn run(&mut self, chunk: Chunk) -> Result<(), String> {
+    fn pop(&mut self) -> Result<Value, SageError> {
+        self.stack.pop().ok_or_else(|| self.runtime_error("Stack underflow"))
+    }
+    
+    // where the callee or receiver sits, below `arg_count` arguments
+    fn callee_index(&self, arg_count: usize) -> Result<usize, SageError> {
+        self.stack.len().checked_sub(arg_count + 1).ok_or_else(|| self.runtime_error("Stack underflow"))
+    }
+    
+    // run a top-level chunk and return the value it returned.
+    // on error the stack and frames are cleared so the vm can be reused.
+    pub fn run(&mut self, chunk: Chunk) -> Result<Value, SageError> {
         if self.debug {
             chunk.disassemble();
         }
         
+        let function = Function {
+            name: chunk.name.clone(),
+            param_count: 0,
+            chunk: Rc::new(chunk),
+            captures: Vec::new(),
+            upvalues: Vec::new(),
+            class_name: None,
+        };
         let frame = CallFrame {
-            chunk,
+            function: Rc::new(function),
             ip: 0,
             stack_offset: 0,
-            class_context: None,
+            discard_result: false,
         };
         self.frames.push(frame);
         
-        self.execute()
+        match self.execute(0) {
+            Ok(()) => Ok(self.stack.pop().unwrap_or(Value::Null)),
+            Err(e) => {
+                // closures made before the error keep the values they captured,
+                // rather than pointing into a stack the next run reuses
+                self.close_upvalues(0);
+                self.frames.clear();
+                self.stack.clear();
+                Err(e)
+            }
+        }
+    }
+    
+    // format a value for printing or joining onto a string, calling
+    // toString on instances of classes that define it
+    pub fn stringify(&mut self, value: &Value) -> Result<String, SageError> {
+        value.format_with(&mut |v| self.call_to_string(v))
+    }
+    
+    fn call_to_string(&mut self, value: &Value) -> Result<Option<String>, SageError> {
+        let method = match value {
+            Value::Instance { class, .. } => class.find_method("toString").cloned(),
+            _ => None,
+        };
+        let Some(method) = method else {
+            return Ok(None);
+        };
+        let bound = Value::BoundMethod {
+            receiver: Box::new(value.clone()),
+            method: Box::new(method),
+        };
+        match self.call(bound, Vec::new())? {
+            Value::String(s) => Ok(Some(s)),
+            other => Err(self.runtime_error(&format!("toString must return a string, got {}", Self::type_name(&other)))),
+        }
+    }
+    
+    // call a function, method or class from rust and run it to completion.
+    // on error the vm is put back the way it was before the call.
+    pub fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, SageError> {
+        if self.nested_calls >= MAX_NESTED_CALLS {
+            return Err(self.runtime_error("Stack overflow"));
+        }
+        let base_frames = self.frames.len();
+        let base_stack = self.stack.len();
+        let arg_count = args.len();
+        self.stack.push(callee);
+        self.stack.extend(args);
+        
+        self.nested_calls += 1;
+        let result = self.call_value(arg_count).and_then(|()| self.execute(base_frames));
+        self.nested_calls -= 1;
+        match result {
+            Ok(()) => Ok(self.stack.pop().unwrap_or(Value::Null)),
+            Err(e) => {
+                self.frames.truncate(base_frames);
+                self.close_upvalues(base_stack);
+                self.stack.truncate(base_stack);
+                Err(e)
+            }
+        }
     }
     
-    fn execute(&mut self) -> Result<(), String> {
-        loop {
-            if self.frames.is_empty() {
+    // run until the call stack is back down to `base` frames
+    fn execute(&mut self, base: usize) -> Result<(), SageError> {
+        'frames: loop {
+            if self.frames.len() <= base {
                 return Ok(());
             }
             
+            // the running frame's function, ip and stack offset are kept in
+            // locals until a call or return switches frames. the frame's own
+            // ip is only brought up to date before instructions that can fail
+            // or run other code, since that's when errors and calls read it.
             let frame_idx = self.frames.len() - 1;
-            let ip = self.frames[frame_idx].ip;
+            let function = self.frames[frame_idx].function.clone();
+            let stack_offset = self.frames[frame_idx].stack_offset;
+            let mut ip = self.frames[frame_idx].ip;
             
-            if ip >= self.frames[frame_idx].chunk.code.len() {
-                self.frames.pop();
-                if self.frames.is_empty() {
-                    return Ok(());
+            loop {
+                if ip >= function.chunk.code.len() {
+                    self.frames.pop();
+                    continue 'frames;
                 }
-                continue;
-            }
-            
-            let instruction = self.frames[frame_idx].chunk.code[ip].clone();
-            self.frames[frame_idx].ip += 1;
-            
-            if self.debug {
-                println!("[DEBUG] Stack before {:?}: {:?}", instruction, self.stack);
-            }
-            
-            match instruction {
-                OpCode::LoadConst(idx) => {
-                    let constant = self.frames[frame_idx].chunk.constants[idx].clone();
-                    self.stack.push(constant);
+                let Some(instruction) = OpCode::decode(&function.chunk.code, &mut ip) else {
+                    self.frames[frame_idx].ip = ip;
+                    return Err(self.runtime_error("Invalid instruction"));
+                };
+                if !Self::is_plain(&instruction) {
+                    self.frames[frame_idx].ip = ip;
                 }
                 
-                OpCode::LoadTrue => {
-                    self.stack.push(Value::Bool(true));
+                if self.debug {
+                    println!("[DEBUG] Stack before {:?}: {:?}", instruction, self.stack);
                 }
                 
-                OpCode::LoadFalse => {
-                    self.stack.push(Value::Bool(false));
-                }
-                
-                OpCode::LoadNull => {
-                    self.stack.push(Value::Null);
-                }
-                
-                OpCode::GetGlobal(idx) => {
-                    let name = if let Value::String(s) = &self.frames[frame_idx].chunk.constants[idx] {
-                        s.clone()
-                    } else {
-                        return Err(self.runtime_error("Invalid global variable name"));
-                    };
+                match instruction {
+                    OpCode::LoadConst(idx) => {
+                        let constant = function.chunk.constants[idx].clone();
+                        self.stack.push(constant);
+                    }
                     
-                    let value = self.globals.get(&name).cloned().unwrap_or(Value::Null);
-                    self.stack.push(value);
-                }
-                
-                OpCode::SetGlobal(idx) => {
-                    let name = if let Value::String(s) = &self.frames[frame_idx].chunk.constants[idx] {
-                        s.clone()
-                    } else {
-                        return Err(self.runtime_error("Invalid global variable name"));
-                    };
+                    OpCode::LoadTrue => {
+                        self.stack.push(Value::Bool(true));
+                    }
                     
-                    let value = self.stack.last().cloned().unwrap_or(Value::Null);
-                    self.globals.insert(name, value);
-                }
-                
-                OpCode::GetLocal(idx) => {
-                    let stack_offset = self.frames[frame_idx].stack_offset;
-                    let value = self.stack.get(stack_offset + idx).cloned().unwrap_or(Value::Null);
-                    self.stack.push(value);
-                }
-                
-                OpCode::SetLocal(idx) => {
-                    let stack_offset = self.frames[frame_idx].stack_offset;
-                    let value = self.stack.last().cloned().unwrap_or(Value::Null);
-                    if stack_offset + idx < self.stack.len() {
-                        self.stack[stack_offset + idx] = value;
-                    } else {
-                        while self.stack.len() <= stack_offset + idx {
-                            self.stack.push(Value::Null);
+                    OpCode::LoadFalse => {
+                        self.stack.push(Value::Bool(false));
+                    }
+                    
+                    OpCode::LoadNull => {
+                        self.stack.push(Value::Null);
+                    }
+                    
+                    OpCode::GetGlobal(slot) => {
+                        match self.globals.get(slot) {
+                            Some(Some(value)) => {
+                                let value = value.clone();
+                                self.stack.push(value);
+                            }
+                            _ => {
+                                self.frames[frame_idx].ip = ip;
+                                let name = self.global_names.borrow().name(slot).to_string();
+                                return Err(self.runtime_error(&format!("Undefined variable '{}'", name)));
+                            }
                         }
-                        self.stack[stack_offset + idx] = value;
                     }
-                }
-                
-                OpCode::Add => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Number(x + y));
+                    
+                    OpCode::SetGlobal(slot) => {
+                        let value = self.stack.last().cloned().unwrap_or(Value::Null);
+                        self.set_global_slot(slot, value);
+                    }
+                    
+                    OpCode::GetLocal(idx) => {
+                        let value = self.stack.get(stack_offset + idx).cloned().unwrap_or(Value::Null);
+                        self.stack.push(value);
+                    }
+                    
+                    OpCode::SetLocal(idx) => {
+                        let value = self.stack.last().cloned().unwrap_or(Value::Null);
+                        if stack_offset + idx < self.stack.len() {
+                            self.stack[stack_offset + idx] = value;
+                        } else {
+                            while self.stack.len() <= stack_offset + idx {
+                                self.stack.push(Value::Null);
+                            }
+                            self.stack[stack_offset + idx] = value;
                         }
-                        (Value::String(x), Value::String(y)) => {
-                            self.stack.push(Value::String(format!("{}{}", x, y)));
+                    }
+                    
+                    OpCode::GetUpvalue(idx) => {
+                        let upvalue = function.upvalues[idx].clone();
+                        let value = match &*upvalue.borrow() {
+                            Upvalue::Open(slot) => self.stack.get(*slot).cloned().unwrap_or(Value::Null),
+                            Upvalue::Closed(value) => value.clone(),
+                        };
+                        self.stack.push(value);
+                    }
+                    
+                    OpCode::SetUpvalue(idx) => {
+                        let value = self.stack.last().cloned().unwrap_or(Value::Null);
+                        let upvalue = function.upvalues[idx].clone();
+                        let mut upvalue = upvalue.borrow_mut();
+                        match &mut *upvalue {
+                            Upvalue::Open(slot) => {
+                                let slot = *slot;
+                                if slot >= self.stack.len() {
+                                    self.stack.resize(slot + 1, Value::Null);
+                                }
+                                self.stack[slot] = value;
+                            }
+                            Upvalue::Closed(closed) => *closed = value,
                         }
-                        (Value::String(x), _) => {
-                            let b_str = match &b {
-                                Value::Number(n) => n.to_string(),
-                                Value::Bool(b) => b.to_string(),
-                                Value::Null => "null".to_string(),
-                                Value::String(s) => s.clone(),
-                                _ => format!("{:?}", b),
-                            };
-                            self.stack.push(Value::String(format!("{}{}", x, b_str)));
+                    }
+                    
+                    OpCode::CloseUpvalues(idx) => {
+                        self.close_upvalues(stack_offset + idx);
+                    }
+                    
+                    OpCode::Add => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Number(x + y));
+                            }
+                            (Value::String(x), Value::String(y)) => {
+                                self.stack.push(Value::String(format!("{}{}", x, y)));
+                            }
+                            (Value::String(_), _) | (_, Value::String(_)) => {
+                                // stringify can call toString
+                                self.frames[frame_idx].ip = ip;
+                                let joined = self.stringify(&a)? + &self.stringify(&b)?;
+                                self.stack.push(Value::String(joined));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        (_, Value::String(y)) => {
-                            let a_str = match &a {
-                                Value::Number(n) => n.to_string(),
-                                Value::Bool(b) => b.to_string(),
-                                Value::Null => "null".to_string(),
-                                Value::String(s) => s.clone(),
-                                _ => format!("{:?}", a),
-                            };
-                            self.stack.push(Value::String(format!("{}{}", a_str, y)));
+                    }
+                    
+                    OpCode::Subtract => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Number(x - y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Subtract => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Number(x - y));
+                    
+                    OpCode::Multiply => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Number(x * y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Multiply => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Number(x * y));
+                    
+                    OpCode::Divide => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                if *y == 0.0 {
+                                    self.frames[frame_idx].ip = ip;
+                                    return Err(self.runtime_error("Division by zero"));
+                                }
+                                self.stack.push(Value::Number(x / y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Divide => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            if *y == 0.0 {
-                                return Err(self.runtime_error("Division by zero"));
+                    
+                    OpCode::Modulo => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Number(x % y));
                             }
-                            self.stack.push(Value::Number(x / y));
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Modulo => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Number(x % y));
+                    
+                    OpCode::Negate => {
+                        let a = self.pop()?;
+                        match a {
+                            Value::Number(x) => self.stack.push(Value::Number(-x)),
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Negate => {
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match a {
-                        Value::Number(x) => self.stack.push(Value::Number(-x)),
-                        _ => self.stack.push(Value::Null),
+                    
+                    OpCode::Not => {
+                        let a = self.pop()?;
+                        self.stack.push(Value::Bool(!a.is_truthy()));
                     }
-                }
-                
-                OpCode::Equal => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    self.stack.push(Value::Bool(self.values_equal(&a, &b)));
-                }
-                
-                OpCode::NotEqual => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    self.stack.push(Value::Bool(!self.values_equal(&a, &b)));
-                }
-                
-                OpCode::Greater => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Bool(x > y));
+                    
+                    OpCode::Equal => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(Value::Bool(self.values_equal(&a, &b)));
+                    }
+                    
+                    OpCode::NotEqual => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(Value::Bool(!self.values_equal(&a, &b)));
+                    }
+                    
+                    OpCode::Greater => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Bool(x > y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::GreaterEqual => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Bool(x >= y));
+                    
+                    OpCode::GreaterEqual => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Bool(x >= y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Less => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Bool(x < y));
+                    
+                    OpCode::Less => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Bool(x < y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::LessEqual => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    match (&a, &b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            self.stack.push(Value::Bool(x <= y));
+                    
+                    OpCode::LessEqual => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        match (&a, &b) {
+                            (Value::Number(x), Value::Number(y)) => {
+                                self.stack.push(Value::Bool(x <= y));
+                            }
+                            _ => self.stack.push(Value::Null),
                         }
-                        _ => self.stack.push(Value::Null),
                     }
-                }
-                
-                OpCode::Jump(addr) => {
-                    let frame_idx = self.frames.len() - 1;
-                    self.frames[frame_idx].ip = addr;
-                }
-                
-                OpCode::JumpIfFalse(addr) => {
-                    let condition = self.stack.last().cloned().unwrap_or(Value::Null);
-                    if !self.is_truthy(&condition) {
-                        let frame_idx = self.frames.len() - 1;
-                        self.frames[frame_idx].ip = addr;
+                    
+                    OpCode::Jump(addr) => {
+                        ip = addr;
                     }
-                }
-                
-                OpCode::JumpIfTrue(addr) => {
-                    let condition = self.stack.last().cloned().unwrap_or(Value::Null);
-                    if self.is_truthy(&condition) {
-                        let frame_idx = self.frames.len() - 1;
-                        self.frames[frame_idx].ip = addr;
+                    
+                    OpCode::JumpIfFalse(addr) => {
+                        let condition = self.stack.last().cloned().unwrap_or(Value::Null);
+                        if !condition.is_truthy() {
+                            ip = addr;
+                        }
                     }
-                }
-                
-                OpCode::Loop(addr) => {
-                    let frame_idx = self.frames.len() - 1;
-                    self.frames[frame_idx].ip = addr;
-                }
-                
-                OpCode::Call(arg_count) => {
-                    let func_index = self.stack.len() - arg_count - 1;
-                    let function = self.stack[func_index].clone();
-                    
-                    match function {
-                        Value::Function { name: _, param_count, chunk } => {
-                            if arg_count != param_count {
-                                return Err(self.runtime_error(&format!("Expected {} arguments but got {}", param_count, arg_count)));
-                            }
-                            
-                            self.stack.remove(func_index);
-                            let stack_offset = self.stack.len() - arg_count;
-                            
-                            let new_frame = CallFrame {
-                                chunk,
-                                ip: 0,
-                                stack_offset,
-                                class_context: None,
-                            };
-                            
-                            self.frames.push(new_frame);
+                    
+                    OpCode::JumpIfTrue(addr) => {
+                        let condition = self.stack.last().cloned().unwrap_or(Value::Null);
+                        if condition.is_truthy() {
+                            ip = addr;
                         }
-                        Value::Class { name, methods, field_access, method_access, static_methods, .. } => {
-                            use std::rc::Rc;
-                            use std::cell::RefCell;
-                            
-                            let instance = Value::Instance {
-                                class_name: name.clone(),
-                                fields: Rc::new(RefCell::new(std::collections::HashMap::new())),
-                                field_access: field_access.clone(),
-                                methods: methods.clone(),
-                                method_access: method_access.clone(),
-                                static_methods: static_methods.clone(),
-                            };
-                            
-                            self.stack.remove(func_index);
-                            
-                            if let Some(constructor) = methods.get("constructor") {
-                                let args_start = self.stack.len() - arg_count;
-                                self.stack.insert(args_start, instance.clone());
-                                
-                                if let Value::Function { param_count, chunk, .. } = constructor {
-                                    if arg_count != *param_count {
-                                        return Err(self.runtime_error(&format!("Expected {} arguments but got {}", param_count, arg_count)));
-                                    }
-                                    
-                                    let stack_offset = args_start;
-                                    let new_frame = CallFrame {
-                                        chunk: chunk.clone(),
-                                        ip: 0,
-                                        stack_offset,
-                                        class_context: Some(name.clone()),
-                                    };
-                                    self.frames.push(new_frame);
+                    }
+                    
+                    OpCode::Loop(addr) => {
+                        ip = addr;
+                    }
+                    
+                    OpCode::Call(arg_count) => {
+                        self.call_value(arg_count)?;
+                        continue 'frames;
+                    }
+                    
+                    OpCode::Closure(idx) => {
+                        let wrapped = function.chunk.constants[idx].clone();
+                        if let Value::Function(wrapped) = wrapped {
+                            let upvalues = wrapped.captures.iter().map(|capture| {
+                                if capture.is_local {
+                                    self.capture_upvalue(stack_offset + capture.index)
+                                } else {
+                                    function.upvalues[capture.index].clone()
                                 }
-                            } else {
-                                self.stack.push(instance);
-                            }
-                        }
-                        Value::BoundMethod { receiver, method } => {
-                            let class_name = if let Value::Instance { class_name, .. } = &*receiver {
-                                Some(class_name.clone())
-                            } else {
-                                None
+                            }).collect();
+                            // the closure shares the function's chunk, only the upvalues are new
+                            let closure = Function {
+                                name: wrapped.name.clone(),
+                                param_count: wrapped.param_count,
+                                chunk: wrapped.chunk.clone(),
+                                captures: wrapped.captures.clone(),
+                                upvalues,
+                                class_name: wrapped.class_name.clone(),
                             };
-                            
-                            self.stack.remove(func_index);
-                            self.stack.insert(self.stack.len() - arg_count, *receiver);
-                            
-                            if let Value::Function { param_count, chunk, .. } = *method {
-                                if arg_count != param_count {
-                                    return Err(self.runtime_error(&format!("Expected {} arguments but got {}", param_count, arg_count)));
-                                }
-                                
-                                let stack_offset = self.stack.len() - arg_count - 1;
-                                let new_frame = CallFrame {
-                                    chunk,
-                                    ip: 0,
-                                    stack_offset,
-                                    class_context: class_name,
-                                };
-                                self.frames.push(new_frame);
-                            } else {
-                                return Err(self.runtime_error("Bound method must wrap a function"));
-                            }
+                            self.stack.push(Value::Function(Rc::new(closure)));
+                        } else {
+                            return Err(self.runtime_error("Closure must wrap a function"));
                         }
-                        _ => return Err(self.runtime_error("Attempted to call non-callable")),
                     }
-                }
-                
-                OpCode::Return => {
-                    let return_value = self.stack.pop().unwrap_or(Value::Null);
-                    let frame = self.frames.pop().ok_or("Frame stack underflow")?;
-                    self.stack.truncate(frame.stack_offset);
-                    self.stack.push(return_value);
-                }
-                
-                OpCode::MakeList(count) => {
-                    let mut items = Vec::new();
-                    for _ in 0..count {
-                        items.push(self.stack.pop().ok_or("Stack underflow")?);
+                    
+                    OpCode::Return => {
+                        let return_value = self.stack.pop().unwrap_or(Value::Null);
+                        let frame = self.frames.pop().ok_or_else(|| self.runtime_error("Frame stack underflow"))?;
+                        self.close_upvalues(frame.stack_offset);
+                        self.stack.truncate(frame.stack_offset);
+                        if !frame.discard_result {
+                            self.stack.push(return_value);
+                        }
+                        continue 'frames;
                     }
-                    items.reverse();
-                    self.stack.push(Value::List(items));
-                }
-                
-                OpCode::BuildRange => {
-                    let end = self.stack.pop().ok_or("Stack underflow")?;
-                    let start = self.stack.pop().ok_or("Stack underflow")?;
-                    
-                    match (&start, &end) {
-                        (Value::Number(a), Value::Number(b)) => {
-                            let mut items = Vec::new();
-                            let start_i = *a as i64;
-                            let end_i = *b as i64;
-                            for i in start_i..=end_i {
-                                items.push(Value::Number(i as f64));
-                            }
-                            self.stack.push(Value::List(items));
+                    
+                    OpCode::MakeList(count) => {
+                        let mut items = Vec::new();
+                        for _ in 0..count {
+                            items.push(self.pop()?);
                         }
-                        _ => self.stack.push(Value::Null),
+                        items.reverse();
+                        self.stack.push(Value::List(Rc::new(RefCell::new(items))));
                     }
-                }
-                
-                OpCode::GetIndex => {
-                    let index = self.stack.pop().ok_or("Stack underflow")?;
-                    let list = self.stack.pop().ok_or("Stack underflow")?;
-                    
-                    match (&list, &index) {
-                        (Value::List(items), Value::Number(idx)) => {
-                            let i = *idx as usize;
-                            let value = items.get(i).cloned().unwrap_or(Value::Null);
-                            self.stack.push(value);
+                    
+                    OpCode::MakeMap(count) => {
+                        let mut pairs = Vec::new();
+                        for _ in 0..count {
+                            let value = self.pop()?;
+                            let key = self.pop()?;
+                            pairs.push((key, value));
+                        }
+                        pairs.reverse();
+                        let mut map = Map::new();
+                        for (key, value) in pairs {
+                            map.insert(self.map_key(&key)?, value);
                         }
-                        _ => self.stack.push(Value::Null),
+                        self.stack.push(Value::Map(Rc::new(RefCell::new(map))));
                     }
-                }
-                
-                OpCode::DefineClass(_name_idx) => {
-                    self.stack.pop();
-                }
-                
-                OpCode::GetProperty(name_idx) => {
-                    let instance = self.stack.pop().ok_or("Stack underflow")?;
-                    let name_value = self.frames[frame_idx].chunk.constants.get(name_idx).ok_or("Invalid constant index")?;
-                    let prop_name = if let Value::String(n) = name_value {
-                        n.clone()
-                    } else {
-                        return Err("Property name must be a string".to_string());
-                    };
                     
-                    match instance {
-                        Value::Instance { fields, methods, field_access, method_access, class_name, .. } => {
-                            let current_context = self.frames.last().and_then(|f| f.class_context.clone());
-                            
-                            if let Some(field_value) = fields.borrow().get(&prop_name) {
-                                if let Some(access) = field_access.get(&prop_name) {
-                                    use crate::parser::ast::AccessModifier;
-                                    match access {
-                                        AccessModifier::Private => {
-                                            if current_context.as_ref() != Some(&class_name) {
-                                                return Err(self.runtime_error(&format!("Cannot access private field '{}' from outside class", prop_name)));
-                                            }
-                                        }
-                                        AccessModifier::Protected => {
-                                            if current_context.is_none() {
-                                                return Err(self.runtime_error(&format!("Cannot access protected field '{}' from outside class hierarchy", prop_name)));
-                                            }
-                                        }
-                                        AccessModifier::Public => {}
-                                    }
+                    OpCode::BuildRange => {
+                        let end = self.pop()?;
+                        let start = self.pop()?;
+                        
+                        match (&start, &end) {
+                            (Value::Number(a), Value::Number(b)) => {
+                                let mut items = Vec::new();
+                                let start_i = *a as i64;
+                                let end_i = *b as i64;
+                                for i in start_i..=end_i {
+                                    items.push(Value::Number(i as f64));
                                 }
-                                self.stack.push(field_value.clone());
+                                self.stack.push(Value::List(Rc::new(RefCell::new(items))));
                             }
-                            else if let Some(method) = methods.get(&prop_name) {
-                                if let Some(access) = method_access.get(&prop_name) {
-                                    use crate::parser::ast::AccessModifier;
-                                    match access {
-                                        AccessModifier::Private => {
-                                            if current_context.as_ref() != Some(&class_name) {
-                                                return Err(self.runtime_error(&format!("Cannot access private method '{}' from outside class", prop_name)));
-                                            }
-                                        }
-                                        AccessModifier::Protected => {
-                                            if current_context.is_none() {
-                                                return Err(self.runtime_error(&format!("Cannot access protected method '{}' from outside class hierarchy", prop_name)));
-                                            }
-                                        }
-                                        AccessModifier::Public => {}
-                                    }
+                            _ => self.stack.push(Value::Null),
+                        }
+                    }
+                    
+                    OpCode::GetIndex => {
+                        let index = self.pop()?;
+                        let object = self.pop()?;
+                        
+                        let value = match &object {
+                            Value::List(items) => {
+                                let items = items.borrow();
+                                let i = self.resolve_index(&index, items.len(), "list")?;
+                                items[i].clone()
+                            }
+                            Value::String(s) => {
+                                let chars: Vec<char> = s.chars().collect();
+                                let i = self.resolve_index(&index, chars.len(), "string")?;
+                                Value::String(chars[i].to_string())
+                            }
+                            Value::Map(map) => {
+                                let key = self.map_key(&index)?;
+                                match map.borrow().get(&key) {
+                                    Some(value) => value.clone(),
+                                    None => return Err(self.runtime_error(&format!("Key {} not found in map", key))),
                                 }
-                                self.stack.push(Value::BoundMethod {
-                                    receiver: Box::new(Value::Instance {
-                                        class_name: "".to_string(),
-                                        fields: fields.clone(),
-                                        field_access: field_access.clone(),
-                                        methods: methods.clone(),
-                                        method_access: method_access.clone(),
-                                        static_methods: HashMap::new(),
-                                    }),
-                                    method: Box::new(method.clone()),
-                                });
-                            } else {
-                                return Err(self.runtime_error(&format!("Undefined property '{}'", prop_name)));
                             }
-                        }
-                        Value::Class { static_methods, .. } => {
-                            if let Some(static_method) = static_methods.get(&prop_name) {
-                                self.stack.push(static_method.clone());
-                            } else {
-                                return Err(self.runtime_error(&format!("Undefined static method '{}'", prop_name)));
+                            other => {
+                                return Err(self.runtime_error(&format!("Cannot index into {}", Self::type_name(other))));
+                            }
+                        };
+                        self.stack.push(value);
+                    }
+                    
+                    OpCode::SetIndex => {
+                        let value = self.pop()?;
+                        let index = self.pop()?;
+                        let object = self.pop()?;
+                        
+                        match object {
+                            Value::List(items) => {
+                                let len = items.borrow().len();
+                                let i = self.resolve_index(&index, len, "list")?;
+                                items.borrow_mut()[i] = value.clone();
+                                self.stack.push(value);
+                            }
+                            Value::Map(map) => {
+                                let key = self.map_key(&index)?;
+                                map.borrow_mut().insert(key, value.clone());
+                                self.stack.push(value);
+                            }
+                            Value::String(_) => {
+                                return Err(self.runtime_error("Cannot assign to an index of a string"));
+                            }
+                            other => {
+                                return Err(self.runtime_error(&format!("Cannot index into {}", Self::type_name(&other))));
                             }
                         }
-                        _ => return Err(self.runtime_error("Only instances and classes have properties")),
                     }
-                }
-                
-                OpCode::SetProperty(name_idx) => {
-                    let value = self.stack.pop().ok_or("Stack underflow")?;
-                    let instance = self.stack.pop().ok_or("Stack underflow")?;
-                    let name_value = self.frames[frame_idx].chunk.constants.get(name_idx).ok_or("Invalid constant index")?;
-                    let prop_name = if let Value::String(n) = name_value {
-                        n.clone()
-                    } else {
-                        return Err(self.runtime_error("Property name must be a string"));
-                    };
                     
-                    match instance {
-                        Value::Instance { fields, field_access, class_name, .. } => {
-                            let current_context = self.frames.last().and_then(|f| f.class_context.clone());
-                            
-                            if let Some(access) = field_access.get(&prop_name) {
-                                use crate::parser::ast::AccessModifier;
-                                match access {
-                                    AccessModifier::Private => {
-                                        // private: only accessible within same class
-                                        if current_context.as_ref() != Some(&class_name) {
-                                            return Err(self.runtime_error(&format!("Cannot set private field '{}' from outside class", prop_name)));
-                                        }
-                                    }
-                                    AccessModifier::Protected => {
-                                        // protected: accessible within class and subclasses
-                                        if current_context.is_none() {
-                                            return Err(self.runtime_error(&format!("Cannot set protected field '{}' from outside class hierarchy", prop_name)));
-                                        }
-                                    }
-                                    AccessModifier::Public => {
-                                        // public: accessible from anywhere
-                                        // don't do anything here
-                                    }
-                                }
+                    OpCode::Len => {
+                        let object = self.pop()?;
+                        let len = match &object {
+                            Value::List(items) => items.borrow().len(),
+                            Value::String(s) => s.chars().count(),
+                            Value::Map(map) => map.borrow().len(),
+                            other => {
+                                return Err(self.runtime_error(&format!("Cannot take the length of {}", Self::type_name(other))));
+                            }
+                        };
+                        self.stack.push(Value::Number(len as f64));
+                    }
+                    
+                    OpCode::GetIter => {
+                        let object = self.pop()?;
+                        match object {
+                            Value::List(_) | Value::String(_) => self.stack.push(object),
+                            Value::Map(map) => {
+                                let keys = map.borrow().keys().map(MapKey::to_value).collect();
+                                self.stack.push(Value::List(Rc::new(RefCell::new(keys))));
+                            }
+                            other => {
+                                return Err(self.runtime_error(&format!("Cannot iterate over {}", Self::type_name(&other))));
                             }
-                            fields.borrow_mut().insert(prop_name, value.clone());
-                            self.stack.push(value);
                         }
-                        _ => return Err(self.runtime_error("Only instances have fields")),
                     }
-                }
-                
-                OpCode::GetSuper(name_idx) => {
-                    let name_value = self.frames[frame_idx].chunk.constants.get(name_idx).ok_or("Invalid constant index")?;
-                    let method_name = if let Value::String(n) = name_value {
-                        n.clone()
-                    } else {
-                        return Err(self.runtime_error("Method name must be a string"));
-                    };
                     
-                    let superclass = self.stack.pop().ok_or("Stack underflow")?;
-                    let instance = self.stack.pop().ok_or("Stack underflow")?;
+                    OpCode::GetProperty(name_idx) => {
+                        let instance = self.pop()?;
+                        let name_value = function.chunk.constants.get(name_idx).ok_or_else(|| self.runtime_error("Invalid constant index"))?;
+                        let prop_name = if let Value::String(n) = name_value {
+                            n.clone()
+                        } else {
+                            return Err(self.runtime_error("Property name must be a string"));
+                        };
+                        
+                        let value = self.get_property(instance, prop_name)?;
+                        self.stack.push(value);
+                    }
+                    
+                    OpCode::Invoke(name_idx, arg_count) => {
+                        let name_value = function.chunk.constants.get(name_idx).ok_or_else(|| self.runtime_error("Invalid constant index"))?;
+                        let method_name = if let Value::String(n) = name_value {
+                            n.clone()
+                        } else {
+                            return Err(self.runtime_error("Method name must be a string"));
+                        };
+                        
+                        let receiver_idx = self.callee_index(arg_count)?;
+                        let receiver = self.stack[receiver_idx].clone();
+                        if let Value::List(items) = receiver {
+                            // list methods run natively and mutate the list in place
+                            let args = self.stack.split_off(receiver_idx + 1);
+                            self.stack.pop();
+                            let result = self.call_list_method(&items, &method_name, args)?;
+                            self.stack.push(result);
+                        } else if let Value::Map(map) = receiver {
+                            let args = self.stack.split_off(receiver_idx + 1);
+                            self.stack.pop();
+                            let result = self.call_map_method(&map, &method_name, args)?;
+                            self.stack.push(result);
+                        } else {
+                            let method = self.get_property(receiver, method_name)?;
+                            self.stack[receiver_idx] = method;
+                            self.call_value(arg_count)?;
+                            continue 'frames;
+                        }
+                    }
                     
-                    match superclass {
-                        Value::Class { methods, .. } => {
-                            if let Some(method) = methods.get(&method_name) {
-                                if let Value::Instance { class_name, fields, field_access, methods: inst_methods, method_access, static_methods } = instance {
+                    OpCode::SetProperty(name_idx) => {
+                        let value = self.pop()?;
+                        let instance = self.pop()?;
+                        let name_value = function.chunk.constants.get(name_idx).ok_or_else(|| self.runtime_error("Invalid constant index"))?;
+                        let prop_name = if let Value::String(n) = name_value {
+                            n.clone()
+                        } else {
+                            return Err(self.runtime_error("Property name must be a string"));
+                        };
+                        
+                        match instance {
+                            Value::Instance { fields, class } => {
+                                // the analyzer catches most of these, but not when
+                                // the receiver's class isn't known until now
+                                let Some((owner, access)) = class.find_field_access(&prop_name) else {
+                                    return Err(self.runtime_error(&format!("Cannot assign to undeclared field '{}' of class '{}'", prop_name, class.name)));
+                                };
+                                self.check_access(access, owner, "set", "field", &prop_name)?;
+                                fields.borrow_mut().insert(prop_name, value.clone());
+                            }
+                            Value::Class(class) => {
+                                let Some((owner, access)) = class.find_static_field_access(&prop_name) else {
+                                    return Err(self.runtime_error(&format!("Cannot assign to undeclared static field '{}' of class '{}'", prop_name, class.name)));
+                                };
+                                self.check_access(access, owner, "set", "static field", &prop_name)?;
+                                owner.static_fields.borrow_mut().insert(prop_name, value.clone());
+                            }
+                            _ => return Err(self.runtime_error("Only instances and classes have fields")),
+                        }
+                        self.stack.push(value);
+                    }
+                    
+                    OpCode::GetSuper(name_idx) => {
+                        let name_value = function.chunk.constants.get(name_idx).ok_or_else(|| self.runtime_error("Invalid constant index"))?;
+                        let method_name = if let Value::String(n) = name_value {
+                            n.clone()
+                        } else {
+                            return Err(self.runtime_error("Method name must be a string"));
+                        };
+                        
+                        let superclass = self.pop()?;
+                        let instance = self.pop()?;
+                        
+                        match superclass {
+                            Value::Class(superclass) => {
+                                if !matches!(instance, Value::Instance { .. }) {
+                                    return Err(self.runtime_error("Super can only be used with instances"));
+                                }
+                                if let Some(method) = superclass.find_method(&method_name).cloned() {
                                     self.stack.push(Value::BoundMethod {
-                                        receiver: Box::new(Value::Instance {
-                                            class_name,
-                                            fields,
-                                            field_access,
-                                            methods: inst_methods,
-                                            method_access,
-                                            static_methods,
-                                        }),
-                                        method: Box::new(method.clone()),
+                                        receiver: Box::new(instance),
+                                        method: Box::new(method),
                                     });
+                                } else if method_name == "constructor" {
+                                    // superclasses without a constructor have an
+                                    // implicit one, which takes nothing and does nothing
+                                    self.stack.push(Value::NativeFunction(NativeFunction {
+                                        name: "constructor".to_string(),
+                                        arity: Some(0),
+                                        function: Rc::new(move |_, _| Ok(instance.clone())),
+                                    }));
                                 } else {
-                                    return Err(self.runtime_error("Super can only be used with instances"));
+                                    return Err(self.runtime_error(&format!("Undefined method '{}' in superclass", method_name)));
                                 }
-                            } else {
-                                return Err(self.runtime_error(&format!("Undefined method '{}' in superclass", method_name)));
                             }
+                            _ => return Err(self.runtime_error("Superclass must be a class")),
                         }
-                        _ => return Err(self.runtime_error("Superclass must be a class")),
                     }
-                }
-                
-                OpCode::Inherit => {
-                    let subclass_name_val = self.stack.pop().ok_or("Stack underflow")?;
-                    let superclass = self.stack.pop().ok_or("Stack underflow")?;
-                    
-                    let subclass_name = if let Value::String(n) = subclass_name_val {
-                        n
-                    } else {
-                        return Err(self.runtime_error("Class name must be a string"));
-                    };
                     
-                    if let Value::Class { methods: super_methods, field_access: super_field_access, method_access: super_method_access, static_methods: super_static_methods, .. } = superclass {
-                        if let Some(Value::Class { name, superclass: _, methods: subclass_methods, field_access: subclass_field_access, method_access: subclass_method_access, static_methods: subclass_static_methods }) = self.globals.get(&subclass_name).cloned() {
-                            let mut merged_methods = super_methods.clone();
-                            for (method_name, method_value) in subclass_methods {
-                                merged_methods.insert(method_name, method_value);
-                            }
-                            
-                            // merge static methods
-                            let mut merged_static_methods = super_static_methods.clone();
-                            for (method_name, method_value) in subclass_static_methods {
-                                merged_static_methods.insert(method_name, method_value);
-                            }
-                            
-                            // merge access modifiers
-                            let mut merged_field_access = super_field_access.clone();
-                            for (field_name, access) in subclass_field_access {
-                                merged_field_access.insert(field_name, access);
-                            }
-                            
-                            let mut merged_method_access = super_method_access.clone();
-                            for (method_name, access) in subclass_method_access {
-                                merged_method_access.insert(method_name, access);
-                            }
-                            
-                            let new_class = Value::Class {
-                                name: name.clone(),
-                                superclass: Some(Box::new(Value::Class {
-                                    name: "".to_string(),
-                                    superclass: None,
-                                    field_access: super_field_access.clone(),
-                                    method_access: super_method_access.clone(),
-                                    methods: super_methods.clone(),
-                                    static_methods: super_static_methods.clone(),
-                                })),
-                                field_access: merged_field_access,
-                                method_access: merged_method_access,
-                                methods: merged_methods,
-                                static_methods: merged_static_methods,
-                            };
-                            
-                            self.globals.insert(subclass_name, new_class);
+                    OpCode::New(arg_count) => {
+                        self.instantiate(arg_count)?;
+                        continue 'frames;
+                    }
+                    
+                    OpCode::InstanceOf => {
+                        let class = self.pop()?;
+                        let value = self.pop()?;
+                        let Value::Class(class) = class else {
+                            return Err(self.runtime_error(&format!("Right side of 'instanceof' must be a class, got {}", Self::type_name(&class))));
+                        };
+                        let is_instance = matches!(&value, Value::Instance { class: own, .. } if own.is_subclass_of(&class));
+                        self.stack.push(Value::Bool(is_instance));
+                    }
+                    
+                    OpCode::Inherit => {
+                        let superclass = self.pop()?;
+                        let template = self.pop()?;
+                        
+                        let (Value::Class(superclass), Value::Class(template)) = (superclass, template) else {
+                            return Err(self.runtime_error("Superclass must be a class"));
+                        };
+                        
+                        // the class is made once, linked to its superclass, so every
+                        // instance shares it and sees the same ancestors
+                        let class = Class::new(
+                            template.name.clone(),
+                            Some(superclass),
+                            template.field_access.clone(),
+                            template.method_access.clone(),
+                            template.methods.clone(),
+                            template.static_methods.clone(),
+                            template.static_field_access.clone(),
+                        );
+                        self.stack.push(Value::Class(Rc::new(class)));
+                    }
+                    
+                    OpCode::Method(name_idx, is_static) => {
+                        let Some(Value::String(method_name)) = function.chunk.constants.get(name_idx).cloned() else {
+                            return Err(self.runtime_error("Method name must be a string"));
+                        };
+                        let method = self.pop()?;
+                        let Value::Class(class) = self.pop()? else {
+                            return Err(self.runtime_error("Methods can only be added to a class"));
+                        };
+                        
+                        // the method closes over locals of this call, so the
+                        // class is remade rather than shared between calls
+                        let mut methods = class.methods.clone();
+                        let mut static_methods = class.static_methods.clone();
+                        if is_static != 0 {
+                            static_methods.insert(method_name, method);
+                        } else {
+                            methods.insert(method_name, method);
                         }
-                    } else {
-                        return Err(self.runtime_error("Superclass must be a class"));
+                        let class = Class::new(
+                            class.name.clone(),
+                            class.superclass.clone(),
+                            class.field_access.clone(),
+                            class.method_access.clone(),
+                            methods,
+                            static_methods,
+                            class.static_field_access.clone(),
+                        );
+                        self.stack.push(Value::Class(Rc::new(class)));
+                    }
+                    
+                    OpCode::Pop => {
+                        self.stack.pop();
+                    }
+                    
+                    OpCode::Print => {
+                        let value = self.pop()?;
+                        println!("{}", self.stringify(&value)?);
+                    }
+                    
+                    OpCode::Dup => {
+                        let value = self.stack.last().cloned().ok_or_else(|| self.runtime_error("Stack underflow"))?;
+                        self.stack.push(value);
                     }
                 }
-                
-                OpCode::Pop => {
-                    self.stack.pop();
+            }
+        }
+    }
+    
+    // call the value sitting below `arg_count` arguments on the stack.
+    // script functions push a new frame, which run() then executes.
+    fn call_value(&mut self, arg_count: usize) -> Result<(), SageError> {
+        let func_index = self.callee_index(arg_count)?;
+        let function = self.stack[func_index].clone();
+        
+        match function {
+            Value::Function(function) => {
+                if arg_count != function.param_count {
+                    return Err(self.runtime_error(&format!("Expected {} arguments but got {}", function.param_count, arg_count)));
                 }
                 
-                OpCode::Print => {
-                    let value = self.stack.pop().ok_or("Stack underflow")?;
-                    println!("{:?}", value);
+                self.stack.remove(func_index);
+                let stack_offset = self.stack.len() - arg_count;
+                
+                let new_frame = CallFrame {
+                    function,
+                    ip: 0,
+                    stack_offset,
+                    discard_result: false,
+                };
+                
+                self.push_frame(new_frame)?;
+            }
+            Value::Class(class) => {
+                return Err(self.runtime_error(&format!("Class '{}' must be instantiated with 'new'", class.name)));
+            }
+            Value::BoundMethod { receiver, method } => {
+                self.stack.remove(func_index);
+                self.stack.insert(self.stack.len() - arg_count, *receiver);
+                
+                if let Value::Function(function) = *method {
+                    if arg_count != function.param_count {
+                        return Err(self.runtime_error(&format!("Expected {} arguments but got {}", function.param_count, arg_count)));
+                    }
+                    
+                    let stack_offset = self.stack.len() - arg_count - 1;
+                    let new_frame = CallFrame {
+                        function,
+                        ip: 0,
+                        stack_offset,
+                        discard_result: false,
+                    };
+                    self.push_frame(new_frame)?;
+                } else {
+                    return Err(self.runtime_error("Bound method must wrap a function"));
+                }
+            }
+            Value::NativeFunction(native) => {
+                if let Some(arity) = native.arity.filter(|&arity| arity != arg_count) {
+                    return Err(self.runtime_error(&format!("Expected {} arguments but got {}", arity, arg_count)));
+                }
+                let args = self.stack.split_off(func_index + 1);
+                self.stack.pop();
+                let result = (native.function)(self, &args).map_err(|message| self.runtime_error(&message))?;
+                self.stack.push(result);
+            }
+            _ => return Err(self.runtime_error("Attempted to call non-callable")),
+        }
+        Ok(())
+    }
+    
+    // create an instance of the class sitting below `arg_count` arguments on
+    // the stack. the instance takes the class's place, and becomes `this`
+    // for the constructor if the class or a superclass has one.
+    fn instantiate(&mut self, arg_count: usize) -> Result<(), SageError> {
+        let class_index = self.callee_index(arg_count)?;
+        let class = match &self.stack[class_index] {
+            Value::Class(class) => class.clone(),
+            other => return Err(self.runtime_error(&format!("Cannot use 'new' with {}", Self::type_name(other)))),
+        };
+        
+        let constructor = match class.find_method("constructor") {
+            Some(Value::Function(constructor)) => {
+                if arg_count != constructor.param_count {
+                    return Err(self.runtime_error(&format!("Expected {} arguments but got {}", constructor.param_count, arg_count)));
+                }
+                Some(constructor.clone())
+            }
+            Some(_) => return Err(self.runtime_error("Constructor must be a function")),
+            None if arg_count > 0 => {
+                return Err(self.runtime_error(&format!("Expected 0 arguments but got {}", arg_count)));
+            }
+            None => None,
+        };
+        
+        let instance = Value::Instance {
+            class: class.clone(),
+            fields: Rc::new(RefCell::new(HashMap::new())),
+        };
+        self.stack[class_index] = instance.clone();
+        
+        if let Some(constructor) = constructor {
+            let new_frame = CallFrame {
+                function: constructor,
+                ip: 0,
+                stack_offset: class_index,
+                discard_result: false,
+            };
+            self.push_frame(new_frame)?;
+        }
+        
+        // fields get their starting values before the constructor runs, so
+        // their frames go above its one, the root class's on top to run
+        // first. each has its own copy of the instance as `this`.
+        for initializer in class.field_initializers().into_iter().rev() {
+            let Value::Function(initializer) = initializer else {
+                return Err(self.runtime_error("Field initializer must be a function"));
+            };
+            let stack_offset = self.stack.len();
+            self.stack.push(instance.clone());
+            let new_frame = CallFrame {
+                function: initializer.clone(),
+                ip: 0,
+                stack_offset,
+                discard_result: true,
+            };
+            self.push_frame(new_frame)?;
+        }
+        Ok(())
+    }
+    
+    fn push_frame(&mut self, frame: CallFrame) -> Result<(), SageError> {
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.runtime_error("Stack overflow"));
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+    
+    // look up a field or method on an instance, or a static field or method
+    // on a class
+    fn get_property(&self, object: Value, prop_name: String) -> Result<Value, SageError> {
+        match object {
+            Value::Instance { ref fields, ref class } => {
+                if prop_name == "class" {
+                    return Ok(Value::Class(class.clone()));
                 }
                 
-                OpCode::Dup => {
-                    let value = self.stack.last().cloned().ok_or("Stack underflow")?;
-                    self.stack.push(value);
+                if let Some(field_value) = fields.borrow().get(&prop_name) {
+                    if let Some((owner, access)) = class.find_field_access(&prop_name) {
+                        self.check_access(access, owner, "access", "field", &prop_name)?;
+                    }
+                    Ok(field_value.clone())
+                }
+                else if let Some(method) = class.find_method(&prop_name) {
+                    if let Some((owner, access)) = class.find_method_access(&prop_name) {
+                        self.check_access(access, owner, "access", "method", &prop_name)?;
+                    }
+                    Ok(Value::BoundMethod {
+                        method: Box::new(method.clone()),
+                        receiver: Box::new(object.clone()),
+                    })
+                } else {
+                    Err(self.runtime_error(&format!("Undefined property '{}'", prop_name)))
                 }
             }
+            Value::Class(class) => {
+                if let Some((owner, access)) = class.find_static_field_access(&prop_name) {
+                    self.check_access(access, owner, "access", "static field", &prop_name)?;
+                    return Ok(owner.static_fields.borrow().get(&prop_name).cloned().unwrap_or(Value::Null));
+                }
+                if let Some(static_method) = class.find_static_method(&prop_name) {
+                    if let Some((owner, access)) = class.find_method_access(&prop_name) {
+                        self.check_access(access, owner, "access", "method", &prop_name)?;
+                    }
+                    Ok(static_method.clone())
+                } else {
+                    Err(self.runtime_error(&format!("Undefined static member '{}'", prop_name)))
+                }
+            }
+            _ => Err(self.runtime_error("Only instances and classes have properties")),
         }
     }
     
-    fn is_truthy(&self, value: &Value) -> bool {
+    // private members can only be used by code written in the class that
+    // declares them, protected ones by code written in any class
+    fn check_access(&self, access: &AccessModifier, owner: &Class, verb: &str, kind: &str, name: &str) -> Result<(), SageError> {
+        let context = self.frames.last().and_then(|frame| frame.function.class_name.as_deref());
+        match access {
+            AccessModifier::Private if context != Some(owner.name.as_str()) => {
+                Err(self.runtime_error(&format!("Cannot {} private {} '{}' from outside class", verb, kind, name)))
+            }
+            AccessModifier::Protected if context.is_none() => {
+                Err(self.runtime_error(&format!("Cannot {} protected {} '{}' from outside class hierarchy", verb, kind, name)))
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    fn call_list_method(&self, items: &Rc<RefCell<Vec<Value>>>, name: &str, mut args: Vec<Value>) -> Result<Value, SageError> {
+        let arity = match name {
+            "push" | "remove" => 1,
+            "pop" => 0,
+            "insert" => 2,
+            _ => return Err(self.runtime_error(&format!("Undefined list method '{}'", name))),
+        };
+        if args.len() != arity {
+            return Err(self.runtime_error(&format!("Expected {} arguments but got {}", arity, args.len())));
+        }
+        
+        let len = items.borrow().len();
+        match name {
+            "push" => {
+                items.borrow_mut().push(args.remove(0));
+                Ok(Value::Null)
+            }
+            "pop" => items.borrow_mut().pop().ok_or_else(|| self.runtime_error("Cannot pop from an empty list")),
+            "insert" => {
+                // inserting at the length appends
+                let i = self.resolve_index(&args[0], len + 1, "list")?;
+                items.borrow_mut().insert(i, args.remove(1));
+                Ok(Value::Null)
+            }
+            _ => {
+                let i = self.resolve_index(&args[0], len, "list")?;
+                Ok(items.borrow_mut().remove(i))
+            }
+        }
+    }
+    
+    fn call_map_method(&self, map: &Rc<RefCell<Map>>, name: &str, args: Vec<Value>) -> Result<Value, SageError> {
+        let arity = match name {
+            "keys" | "values" => 0,
+            "has" => 1,
+            _ => return Err(self.runtime_error(&format!("Undefined map method '{}'", name))),
+        };
+        if args.len() != arity {
+            return Err(self.runtime_error(&format!("Expected {} arguments but got {}", arity, args.len())));
+        }
+        
+        let map = map.borrow();
+        let items = match name {
+            "keys" => map.keys().map(MapKey::to_value).collect(),
+            "values" => map.values().cloned().collect(),
+            // values that can't be keys are never in the map
+            _ => return Ok(Value::Bool(MapKey::from_value(&args[0]).is_some_and(|key| map.contains_key(&key)))),
+        };
+        Ok(Value::List(Rc::new(RefCell::new(items))))
+    }
+    
+    fn map_key(&self, value: &Value) -> Result<MapKey, SageError> {
+        MapKey::from_value(value).ok_or_else(|| {
+            self.runtime_error(&format!("Map keys must be strings, numbers or booleans, got {}", Self::type_name(value)))
+        })
+    }
+    
+    // reuse the open upvalue for a stack slot if there is one, so closures
+    // capturing the same variable share it
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self.open_upvalues.iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+    
+    // move the values of captured stack slots at or above `from` into their
+    // upvalues, since those slots are about to go away
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) => slot,
+                Upvalue::Closed(_) => return false,
+            };
+            if slot < from {
+                return true;
+            }
+            let value = stack.get(slot).cloned().unwrap_or(Value::Null);
+            *upvalue.borrow_mut() = Upvalue::Closed(value);
+            false
+        });
+    }
+    
+    // turn an index value into a position in a sequence of the given length.
+    // negative indices count back from the end.
+    fn resolve_index(&self, index: &Value, len: usize, kind: &str) -> Result<usize, SageError> {
+        let n = match index {
+            Value::Number(n) if n.fract() == 0.0 => *n,
+            Value::Number(_) => {
+                return Err(self.runtime_error(&format!("Index must be a whole number, got {}", index)));
+            }
+            other => {
+                return Err(self.runtime_error(&format!("Index must be an integer, got {}", Self::type_name(other))));
+            }
+        };
+        let i = if n < 0.0 { n + len as f64 } else { n };
+        if i < 0.0 || i >= len as f64 {
+            return Err(self.runtime_error(&format!("Index {} out of bounds for {} of length {}", n, kind, len)));
+        }
+        Ok(i as usize)
+    }
+    
+    fn type_name(value: &Value) -> &'static str {
         match value {
-            Value::Bool(b) => *b,
-            Value::Number(n) => *n != 0.0,
-            Value::Null => false,
-            Value::List(l) => !l.is_empty(),
-            Value::String(s) => !s.is_empty(),
-            _ => true,
+            Value::Number(_) => "a number",
+            Value::Bool(_) => "a boolean",
+            Value::String(_) => "a string",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+            Value::Function(_) => "a function",
+            Value::Class(_) => "a class",
+            Value::Instance { .. } => "an instance",
+            Value::BoundMethod { .. } => "a method",
+            Value::NativeFunction(_) => "a function",
+            Value::Null => "null",
         }
     }
     
     fn values_equal(&self, a: &Value, b: &Value) -> bool {
+        self.values_equal_inner(a, b, &mut Vec::new())
+    }
+    
+    // `comparing` holds the pairs of lists and maps whose comparison is in
+    // progress. meeting one again means following a cycle, which can't make
+    // them differ, so it counts as equal rather than recursing forever.
+    fn values_equal_inner(&self, a: &Value, b: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
         match (a, b) {
             (Value::Number(x), Value::Number(y)) => x == y,
             (Value::Bool(x), Value::Bool(y)) => x == y,
             (Value::String(x), Value::String(y)) => x == y,
             (Value::Null, Value::Null) => true,
+            (Value::List(x), Value::List(y)) => {
+                let pair = (Rc::as_ptr(x) as *const (), Rc::as_ptr(y) as *const ());
+                if Rc::ptr_eq(x, y) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                let (x, y) = (x.borrow(), y.borrow());
+                let equal = x.len() == y.len()
+                    && x.iter().zip(y.iter()).all(|(a, b)| self.values_equal_inner(a, b, comparing));
+                comparing.pop();
+                equal
+            }
+            (Value::Map(x), Value::Map(y)) => {
+                let pair = (Rc::as_ptr(x) as *const (), Rc::as_ptr(y) as *const ());
+                if Rc::ptr_eq(x, y) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                // same entries, in any order
+                let (x, y) = (x.borrow(), y.borrow());
+                let equal = x.len() == y.len() && x.keys().all(|key| {
+                    matches!((x.get(key), y.get(key)), (Some(a), Some(b)) if self.values_equal_inner(a, b, comparing))
+                });
+                comparing.pop();
+                equal
+            }
+            // classes, instances and functions are equal only to themselves
+            (Value::Class(x), Value::Class(y)) => Rc::ptr_eq(x, y),
+            (Value::Instance { fields: x, .. }, Value::Instance { fields: y, .. }) => Rc::ptr_eq(x, y),
+            (Value::Function(x), Value::Function(y)) => Rc::ptr_eq(x, y),
             _ => false,
         }
     }
 }
+